@@ -1,49 +1,94 @@
+use crate::gfx::image::Image;
 use process_path::get_executable_path;
 use sdl2::image::LoadTexture;
-use sdl2::render::{Texture, TextureCreator};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Texture, TextureCreator};
 use sdl2::video::WindowContext;
+use std::ops::Range;
 use std::path::PathBuf;
 
-const AGENT_SPRITE_PATH: &str = "assets/agent.png";
-const AGENT_EYES_SPRITE_PATH: &str = "assets/agent_eyes.png";
+pub const AGENT_SPRITE_PATH: &str = "assets/agent.png";
+pub const AGENT_EYES_SPRITE_PATH: &str = "assets/agent_eyes.png";
 
 /// Struct for keeping track of SDL2 assets.
 pub struct Assets<'a> {
     pub agent_sprite: Texture<'a>,
     pub agent_eyes_sprite: Texture<'a>,
+    creator: &'a TextureCreator<WindowContext>,
+    /// The streaming texture of the plant grid.
+    plant_grid: Option<Texture<'a>>,
+}
+
+/// Returns the full path of an asset, such as `AGENT_SPRITE_PATH`.
+/// NOTE: The executable must be three layers deeper than the "assets" directory,
+///       for example ./target/debug/mini-universe next to ./assets.
+pub fn asset_path(path: &str) -> PathBuf {
+    let mut pathbuf = PathBuf::from(
+        get_executable_path()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap(),
+    );
+    pathbuf.push(path);
+    pathbuf
 }
 
 impl<'a> Assets<'a> {
     /// Load assets from disk.
-    /// NOTE: The executable must be three layers deeper than the "assets" directory,
-    ///       for example ./target/debug/mini-universe next to ./assets.
-    pub fn load(creator: &'a mut TextureCreator<WindowContext>) -> Assets<'a> {
-        let assets_dir_pathbuf = PathBuf::from(
-            get_executable_path()
-                .unwrap()
-                .parent()
-                .unwrap()
-                .parent()
-                .unwrap()
-                .parent()
-                .unwrap(),
-        );
-
-        let mut agent_sprite_pathbuf = assets_dir_pathbuf.clone();
-        agent_sprite_pathbuf.push(AGENT_SPRITE_PATH);
+    pub fn load(creator: &'a TextureCreator<WindowContext>) -> Assets<'a> {
         let agent_sprite = creator
-            .load_texture(agent_sprite_pathbuf.as_path())
+            .load_texture(asset_path(AGENT_SPRITE_PATH).as_path())
             .unwrap();
-
-        let mut agent_eyes_sprite_pathbuf = assets_dir_pathbuf.clone();
-        agent_eyes_sprite_pathbuf.push(AGENT_EYES_SPRITE_PATH);
         let agent_eyes_sprite = creator
-            .load_texture(agent_eyes_sprite_pathbuf.as_path())
+            .load_texture(asset_path(AGENT_EYES_SPRITE_PATH).as_path())
             .unwrap();
 
         Assets {
             agent_sprite,
             agent_eyes_sprite,
+            creator,
+            plant_grid: None,
+        }
+    }
+
+    /// Returns the texture of the plant grid, updated to an image of it.
+    /// Only the rows that changed since the last update are uploaded, unless the size changed.
+    pub fn plant_grid_texture(&mut self, image: &Image, changed_rows: Range<u32>) -> &Texture<'a> {
+        let size = image.size;
+        let pitch = size.w as usize * 4;
+        let changed_rows = match &self.plant_grid {
+            Some(texture)
+                if texture.query().width == size.w && texture.query().height == size.h =>
+            {
+                changed_rows
+            }
+            _ => {
+                let mut texture = self
+                    .creator
+                    .create_texture_streaming(PixelFormatEnum::RGBA32, size.w, size.h)
+                    .unwrap();
+                texture.set_blend_mode(BlendMode::None);
+                self.plant_grid = Some(texture);
+                0..size.h
+            }
+        };
+
+        let texture = self.plant_grid.as_mut().unwrap();
+        if changed_rows.end > changed_rows.start {
+            let rect = Rect::new(
+                0,
+                changed_rows.start as i32,
+                size.w,
+                changed_rows.end - changed_rows.start,
+            );
+            let bytes = changed_rows.start as usize * pitch..changed_rows.end as usize * pitch;
+            texture.update(rect, &image.pixels[bytes], pitch).unwrap();
         }
+        texture
     }
 }
@@ -0,0 +1,133 @@
+use crate::gfx::draw_target::DrawTarget;
+use crate::gfx::font::{char_size, draw_text, text_size};
+use crate::gfx::panel::{draw_panel, BORDER_COLOR, PADDING, TEXT_COLOR, TEXT_SCALE};
+use crate::gfx::view::View;
+use crate::util::{Rect2i, Size2i, Vec2i};
+use crate::world::agent::{Command, Percept};
+use crate::world::{Agent, World};
+use sdl2::pixels::Color;
+use strum::{EnumCount, IntoEnumIterator};
+
+const NODE_SIZE: u32 = 12;
+/// The horizontal space between the input and output nodes, in pixels.
+const EDGES_WIDTH: u32 = 56;
+
+/// Returns the color of a node with the given activation: white for positive values and red
+/// for negative values, brighter the further from zero.
+fn activation_color(activation: f32) -> Color {
+    let brightness = (activation.abs().min(1.0) * 255.0) as u8;
+    if activation >= 0.0 {
+        Color::RGB(brightness, brightness, brightness)
+    } else {
+        Color::RGB(brightness, 0, 0)
+    }
+}
+
+/// Returns the color of an edge with the given weight: green for positive weights and red
+/// for negative weights, brighter the stronger.
+fn weight_color(weight: f32) -> Color {
+    let brightness = 40 + (weight.abs().min(1.0) * 215.0) as u8;
+    if weight >= 0.0 {
+        Color::RGB(0, brightness, 0)
+    } else {
+        Color::RGB(brightness, 0, 0)
+    }
+}
+
+/// Draws the brain of an agent in the top right corner of the window, starting at `top` so it
+/// can sit below the minimap: the percepts on the left, the commands on the right and the
+/// weights as edges between them. The nodes show what the agent currently senses and does.
+pub fn draw_brain(
+    target: &mut impl DrawTarget,
+    view: &View,
+    agent: &Agent,
+    world: &World,
+    top: i32,
+) {
+    let (percepts, commands) = agent.percepts_and_commands(&world.plant_grid);
+    let weights = agent.brain_weights();
+    let line_height = char_size(TEXT_SCALE).h;
+    let percept_labels_width = Percept::iter()
+        .map(|percept| text_size(percept.as_ref(), TEXT_SCALE).w)
+        .max()
+        .unwrap_or(0);
+    let command_labels_width = Command::iter()
+        .map(|command| text_size(command.as_ref(), TEXT_SCALE).w)
+        .max()
+        .unwrap_or(0);
+
+    let panel_size = Size2i::new(
+        percept_labels_width + NODE_SIZE * 2 + EDGES_WIDTH + command_labels_width + PADDING * 4,
+        Percept::COUNT as u32 * line_height + PADDING * 2,
+    );
+    let panel_rect = Rect2i::new(
+        view.window_size.w as i32 - panel_size.w as i32,
+        top,
+        panel_size.w,
+        panel_size.h,
+    );
+    draw_panel(target, panel_rect);
+
+    let inputs_x = panel_rect.x + (PADDING * 2 + percept_labels_width) as i32;
+    let outputs_x = inputs_x + (NODE_SIZE + EDGES_WIDTH) as i32;
+    // Every node has a line of its own. The fewer commands are centred vertically.
+    let line_y = |idx: usize, count: usize| {
+        let first_line = (Percept::COUNT - count) as u32 * line_height / 2;
+        panel_rect.y + (PADDING + first_line + idx as u32 * line_height) as i32
+    };
+    let node_y =
+        |idx: usize, count: usize| line_y(idx, count) + (line_height - NODE_SIZE) as i32 / 2;
+
+    for percept in Percept::iter() {
+        let from = Vec2i::new(
+            inputs_x + NODE_SIZE as i32,
+            node_y(percept as usize, Percept::COUNT) + NODE_SIZE as i32 / 2,
+        );
+        for command in Command::iter() {
+            let to = Vec2i::new(
+                outputs_x,
+                node_y(command as usize, Command::COUNT) + NODE_SIZE as i32 / 2,
+            );
+            let weight = weights[percept as usize * Command::COUNT + command as usize];
+            target.draw_line(from, to, weight_color(weight));
+        }
+    }
+
+    for percept in Percept::iter() {
+        let label_width = text_size(percept.as_ref(), TEXT_SCALE).w as i32;
+        draw_text(
+            target,
+            inputs_x - PADDING as i32 - label_width,
+            line_y(percept as usize, Percept::COUNT),
+            percept.as_ref(),
+            TEXT_COLOR,
+            TEXT_SCALE,
+        );
+        let node_rect = Rect2i::new(
+            inputs_x,
+            node_y(percept as usize, Percept::COUNT),
+            NODE_SIZE,
+            NODE_SIZE,
+        );
+        target.fill_rect(node_rect, activation_color(percepts[percept as usize]));
+        target.draw_rect(node_rect, BORDER_COLOR);
+    }
+    for command in Command::iter() {
+        draw_text(
+            target,
+            outputs_x + (NODE_SIZE + PADDING) as i32,
+            line_y(command as usize, Command::COUNT),
+            command.as_ref(),
+            TEXT_COLOR,
+            TEXT_SCALE,
+        );
+        let node_rect = Rect2i::new(
+            outputs_x,
+            node_y(command as usize, Command::COUNT),
+            NODE_SIZE,
+            NODE_SIZE,
+        );
+        target.fill_rect(node_rect, activation_color(commands[command as usize]));
+        target.draw_rect(node_rect, BORDER_COLOR);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::gfx::draw_target::DrawTarget;
+use crate::gfx::font::{char_size, draw_text};
+use crate::gfx::panel::{draw_panel, BORDER_COLOR, PADDING, TEXT_COLOR, TEXT_SCALE};
+use crate::gfx::view::View;
+use crate::util::{Rect2i, Size2i, Vec2i};
+use crate::world::history::{Sample, HISTORY_LENGTH};
+use crate::world::{Gene, History};
+use sdl2::pixels::Color;
+use strum::IntoEnumIterator;
+
+/// The scale of the labels with the range of a chart.
+const LABEL_SCALE: u32 = 1;
+const PLOT_WIDTH: u32 = 240;
+const PLOT_HEIGHT: u32 = 48;
+/// The width of the column with the range of a chart, right of the plot.
+const LABELS_WIDTH: u32 = 60;
+
+/// A quantity of the history that can be charted.
+#[derive(Copy, Clone)]
+enum Series {
+    Population,
+    MeanEnergy,
+    PlantDensity,
+    MeanGene(Gene),
+}
+
+impl Series {
+    fn name(self) -> String {
+        match self {
+            Series::Population => "population".to_string(),
+            Series::MeanEnergy => "mean energy".to_string(),
+            Series::PlantDensity => "plant density".to_string(),
+            Series::MeanGene(gene) => format!("mean {}", gene.as_ref()),
+        }
+    }
+
+    fn value(self, sample: &Sample) -> f32 {
+        match self {
+            Series::Population => sample.population as f32,
+            Series::MeanEnergy => sample.mean_energy,
+            Series::PlantDensity => sample.plant_density,
+            Series::MeanGene(gene) => sample.mean_genes[gene as usize],
+        }
+    }
+
+    fn format(self, value: f32) -> String {
+        match self {
+            Series::Population => format!("{}", value),
+            _ => format!("{:.3}", value),
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Series::Population => Color::RGB(230, 230, 230),
+            Series::MeanEnergy => Color::RGB(230, 200, 60),
+            Series::PlantDensity => Color::RGB(80, 200, 80),
+            Series::MeanGene(_) => Color::RGB(80, 160, 230),
+        }
+    }
+}
+
+/// A panel of line charts of the history of the world: the population, the mean energy,
+/// the plant density and the mean value of one gene at a time.
+pub struct Charts {
+    gene: Gene,
+}
+
+impl Charts {
+    pub fn new() -> Charts {
+        Charts {
+            gene: Gene::iter().next().unwrap(),
+        }
+    }
+
+    /// Charts the next gene, starting over after the last one.
+    pub fn next_gene(&mut self) {
+        let mut genes = Gene::iter().cycle();
+        genes.find(|gene| *gene == self.gene);
+        self.gene = genes.next().unwrap();
+    }
+
+    /// Draws the charts in the bottom left corner of the window.
+    pub fn draw(&self, target: &mut impl DrawTarget, view: &View, history: &History) {
+        let series = [
+            Series::Population,
+            Series::MeanEnergy,
+            Series::PlantDensity,
+            Series::MeanGene(self.gene),
+        ];
+        let chart_height = char_size(TEXT_SCALE).h + PLOT_HEIGHT;
+        let panel_size = Size2i::new(
+            PLOT_WIDTH + LABELS_WIDTH + PADDING * 3,
+            series.len() as u32 * (chart_height + PADDING) + PADDING,
+        );
+        let panel_rect = Rect2i::new(
+            0,
+            view.window_size.h as i32 - panel_size.h as i32,
+            panel_size.w,
+            panel_size.h,
+        );
+        draw_panel(target, panel_rect);
+
+        for (idx, series) in series.iter().enumerate() {
+            let chart_y = panel_rect.y + (PADDING + idx as u32 * (chart_height + PADDING)) as i32;
+            draw_chart(
+                target,
+                panel_rect.x + PADDING as i32,
+                chart_y,
+                *series,
+                history,
+            );
+        }
+    }
+}
+
+impl Default for Charts {
+    fn default() -> Charts {
+        Charts::new()
+    }
+}
+
+/// Draws a single chart with its title and current value above it, and its range beside it.
+/// The samples fill the chart from the left until the history is full, then it scrolls.
+/// Samples without a value, such as means while there are no agents, leave a gap.
+fn draw_chart(target: &mut impl DrawTarget, x: i32, y: i32, series: Series, history: &History) {
+    let samples = history.samples();
+    let current = samples
+        .back()
+        .map_or(String::new(), |sample| series.format(series.value(sample)));
+    draw_text(
+        target,
+        x,
+        y,
+        &format!("{}: {}", series.name(), current),
+        TEXT_COLOR,
+        TEXT_SCALE,
+    );
+
+    let plot_rect = Rect2i::new(
+        x,
+        y + char_size(TEXT_SCALE).h as i32,
+        PLOT_WIDTH,
+        PLOT_HEIGHT,
+    );
+    target.draw_rect(plot_rect, BORDER_COLOR);
+
+    let values = samples.iter().map(|sample| series.value(sample));
+    let (min, max) = values
+        .clone()
+        .filter(|value| value.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+    if min > max {
+        return;
+    }
+    let labels_x = plot_rect.x + (PLOT_WIDTH + PADDING) as i32;
+    let label_height = char_size(LABEL_SCALE).h as i32;
+    draw_text(
+        target,
+        labels_x,
+        plot_rect.y,
+        &series.format(max),
+        TEXT_COLOR,
+        LABEL_SCALE,
+    );
+    draw_text(
+        target,
+        labels_x,
+        plot_rect.y + PLOT_HEIGHT as i32 - label_height,
+        &series.format(min),
+        TEXT_COLOR,
+        LABEL_SCALE,
+    );
+
+    // Leave a pixel of space inside the border of the plot.
+    let range = (max - min).max(f32::EPSILON);
+    let point = |idx: usize, value: f32| {
+        let x = idx as u32 * (PLOT_WIDTH - 3) / (HISTORY_LENGTH as u32 - 1);
+        let y = ((value - min) / range * (PLOT_HEIGHT - 3) as f32) as i32;
+        Vec2i::new(
+            plot_rect.x + 1 + x as i32,
+            plot_rect.y + PLOT_HEIGHT as i32 - 2 - y,
+        )
+    };
+    let mut prev: Option<Vec2i> = None;
+    for (idx, value) in values.enumerate() {
+        let cur = if value.is_finite() {
+            Some(point(idx, value))
+        } else {
+            None
+        };
+        match (prev, cur) {
+            (Some(from), Some(to)) => target.draw_line(from, to, series.color()),
+            (None, Some(to)) => target.draw_line(to, to, series.color()),
+            _ => {}
+        }
+        prev = cur;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn genes_are_cycled() {
+        let mut charts = Charts::new();
+        for _ in Gene::iter() {
+            charts.next_gene();
+        }
+        assert!(charts.gene == Gene::iter().next().unwrap());
+        charts.next_gene();
+        assert!(charts.gene == Gene::iter().nth(1).unwrap());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::gfx::assets::Assets;
+use crate::gfx::image::Image;
+use crate::util::{Rect2i, Vec2i};
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+use std::ops::Range;
+
+/// The sprites that can be drawn.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Sprite {
+    Agent,
+    AgentEyes,
+}
+
+/// Something the world can be drawn on, such as a window or an image in memory.
+/// Colors are drawn as they are, without blending. Sprites are alpha blended.
+pub trait DrawTarget {
+    fn clear(&mut self, color: Color);
+    fn fill_rect(&mut self, rect: Rect2i, color: Color);
+    fn draw_rect(&mut self, rect: Rect2i, color: Color);
+    /// Draws a line of one pixel wide, including both end points.
+    fn draw_line(&mut self, from: Vec2i, to: Vec2i, color: Color);
+    /// Draws an image of the plant grid with one pixel per cell, stretched over every rectangle.
+    /// Only the rows in `changed_rows` differ from the image drawn last time.
+    fn draw_plant_grid(&mut self, image: &Image, changed_rows: Range<u32>, rects: &[Rect2i]);
+    /// Draws a sprite stretched over a rectangle, multiplied by a color.
+    /// The sprite is rotated clockwise around the center of the rectangle by the angle in degrees.
+    fn draw_sprite(&mut self, sprite: Sprite, rect: Rect2i, angle: f64, color_mod: [u8; 3]);
+}
+
+/// Draws on the canvas of a window.
+pub struct CanvasTarget<'a, 'b> {
+    canvas: &'a mut Canvas<Window>,
+    assets: &'a mut Assets<'b>,
+    draw_color: Option<Color>,
+}
+
+impl<'a, 'b> CanvasTarget<'a, 'b> {
+    pub fn new(canvas: &'a mut Canvas<Window>, assets: &'a mut Assets<'b>) -> Self {
+        CanvasTarget {
+            canvas,
+            assets,
+            draw_color: None,
+        }
+    }
+
+    /// Sets the draw color, unless it is already set.
+    fn set_draw_color(&mut self, color: Color) {
+        if self.draw_color != Some(color) {
+            self.canvas.set_draw_color(color);
+            self.draw_color = Some(color);
+        }
+    }
+}
+
+impl DrawTarget for CanvasTarget<'_, '_> {
+    fn clear(&mut self, color: Color) {
+        self.set_draw_color(color);
+        self.canvas.clear();
+    }
+
+    fn fill_rect(&mut self, rect: Rect2i, color: Color) {
+        self.set_draw_color(color);
+        self.canvas
+            .fill_rect(Rect::new(rect.x, rect.y, rect.w, rect.h))
+            .unwrap();
+    }
+
+    fn draw_rect(&mut self, rect: Rect2i, color: Color) {
+        self.set_draw_color(color);
+        self.canvas
+            .draw_rect(Rect::new(rect.x, rect.y, rect.w, rect.h))
+            .unwrap();
+    }
+
+    fn draw_line(&mut self, from: Vec2i, to: Vec2i, color: Color) {
+        self.set_draw_color(color);
+        self.canvas
+            .draw_line(Point::new(from.x, from.y), Point::new(to.x, to.y))
+            .unwrap();
+    }
+
+    fn draw_plant_grid(&mut self, image: &Image, changed_rows: Range<u32>, rects: &[Rect2i]) {
+        // One scaled copy of a texture is much faster than a rectangle per cell.
+        let texture = self.assets.plant_grid_texture(image, changed_rows);
+        for rect in rects.iter() {
+            self.canvas
+                .copy(texture, None, Rect::new(rect.x, rect.y, rect.w, rect.h))
+                .unwrap();
+        }
+    }
+
+    fn draw_sprite(&mut self, sprite: Sprite, rect: Rect2i, angle: f64, color_mod: [u8; 3]) {
+        let texture = match sprite {
+            Sprite::Agent => &mut self.assets.agent_sprite,
+            Sprite::AgentEyes => &mut self.assets.agent_eyes_sprite,
+        };
+        texture.set_color_mod(color_mod[0], color_mod[1], color_mod[2]);
+        self.canvas
+            .copy_ex(
+                texture,
+                None,
+                Rect::new(rect.x, rect.y, rect.w, rect.h),
+                angle,
+                Point::new(rect.w as i32 / 2, rect.h as i32 / 2),
+                false,
+                false,
+            )
+            .unwrap();
+    }
+}
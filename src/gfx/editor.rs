@@ -0,0 +1,318 @@
+use crate::gfx::draw_target::DrawTarget;
+use crate::gfx::view::View;
+use crate::gfx::world::{agent_at_window_pos, window_to_world_pos};
+use crate::util::{calculate_vec2f, vec2f_to_vec2i, Vec2f};
+use crate::world::{Edit, Params, PlantSpecies, World};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use vek::ops::Lerp;
+
+const BRUSH_COLOR: Color = Color::RGBA(255, 255, 255, 255);
+/// The number of line segments the outline of the brush is drawn with.
+const BRUSH_SEGMENTS: u32 = 32;
+/// The radius of the brush in plant cells.
+const DEFAULT_BRUSH_RADIUS: f32 = 2.0;
+const MIN_BRUSH_RADIUS: f32 = 1.0;
+const MAX_BRUSH_RADIUS: f32 = 32.0;
+
+/// What clicking in the world does.
+#[derive(Copy, Clone, PartialEq, Debug, EnumIter)]
+pub enum Tool {
+    /// Selects agents to inspect them.
+    Select,
+    /// Paints plants with the left button and erases them with the right button.
+    Plants,
+    /// Spawns agents or drags them with the left button, deletes them with the right button.
+    Agents,
+    /// Places walls with the left button and removes them with the right button.
+    Walls,
+}
+
+/// What happens while a mouse button is held down.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Stroke {
+    Paint,
+    Erase,
+    PlaceWalls,
+    RemoveWalls,
+    /// Moves the agent with this id.
+    Drag(u64),
+}
+
+/// Edits the world with the mouse, whether the simulation is paused or running.
+pub struct Editor {
+    pub tool: Tool,
+    /// The plant species the brush paints.
+    species: PlantSpecies,
+    brush_radius: f32,
+    /// The index of the next imported genome to spawn, so they take turns.
+    next_genome: usize,
+    stroke: Option<Stroke>,
+    /// Where in the world the stroke was applied last.
+    stroke_pos: Vec2f,
+    /// The position of the mouse on the window.
+    mouse_pos: Vec2f,
+    /// Why the last click did nothing, shown until the next click.
+    message: Option<String>,
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor {
+            tool: Tool::Select,
+            species: PlantSpecies::iter().next().unwrap(),
+            brush_radius: DEFAULT_BRUSH_RADIUS,
+            next_genome: 0,
+            stroke: None,
+            stroke_pos: Vec2f::zero(),
+            mouse_pos: Vec2f::zero(),
+            message: None,
+        }
+    }
+
+    /// Switches to the next tool, starting over after the last one.
+    pub fn next_tool(&mut self) {
+        let mut tools = Tool::iter().cycle();
+        tools.find(|tool| *tool == self.tool);
+        self.tool = tools.next().unwrap();
+        self.stroke = None;
+        self.message = None;
+    }
+
+    /// Paints the next plant species, starting over after the last one.
+    pub fn next_species(&mut self) {
+        let mut species = PlantSpecies::iter().cycle();
+        species.find(|species| *species == self.species);
+        self.species = species.next().unwrap();
+    }
+
+    /// Doubles or halves the radius of the brush.
+    pub fn resize_brush(&mut self, grow: bool) {
+        self.brush_radius = if grow {
+            (self.brush_radius * 2.0).min(MAX_BRUSH_RADIUS)
+        } else {
+            (self.brush_radius / 2.0).max(MIN_BRUSH_RADIUS)
+        };
+    }
+
+    /// Uses the tool at a position on the window. With shift, the agents tool spawns an imported
+    /// genome instead of a random agent. Returns the agent that was spawned or grabbed, so it can
+    /// be selected.
+    pub fn mouse_down(
+        &mut self,
+        button: MouseButton,
+        shift: bool,
+        pos: Vec2f,
+        params: &Params,
+        view: &View,
+        world: &mut World,
+    ) -> Option<u64> {
+        self.mouse_pos = pos;
+        self.message = None;
+        let world_pos = window_to_world_pos(view, pos);
+        let agent = agent_at_window_pos(view, world, pos);
+        self.stroke = match (self.tool, button, agent) {
+            (Tool::Plants, MouseButton::Left, _) => Some(Stroke::Paint),
+            (Tool::Plants, MouseButton::Right, _) => Some(Stroke::Erase),
+            (Tool::Walls, MouseButton::Left, _) => Some(Stroke::PlaceWalls),
+            (Tool::Walls, MouseButton::Right, _) => Some(Stroke::RemoveWalls),
+            (Tool::Agents, MouseButton::Left, Some(id)) => Some(Stroke::Drag(id)),
+            (Tool::Agents, MouseButton::Left, None) => {
+                return self.spawn(params, world, shift, world_pos);
+            }
+            (Tool::Agents, MouseButton::Right, Some(id)) => {
+                world.edit(params, Edit::Remove { id });
+                None
+            }
+            _ => None,
+        };
+        self.stroke_pos = world_pos;
+        self.update(params, view, world);
+        match self.stroke {
+            Some(Stroke::Drag(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Spawns a random agent, or the next imported genome.
+    fn spawn(
+        &mut self,
+        params: &Params,
+        world: &mut World,
+        imported: bool,
+        pos: Vec2f,
+    ) -> Option<u64> {
+        if !imported {
+            return world.edit(params, Edit::Spawn { genome: None, pos });
+        }
+        if params.genomes.is_empty() {
+            self.message = Some("no genomes to spawn, start with genomes=<file>".to_string());
+            return None;
+        }
+        let genome = params.genomes[self.next_genome % params.genomes.len()].clone();
+        self.next_genome += 1;
+        world.edit(
+            params,
+            Edit::Spawn {
+                genome: Some(genome),
+                pos,
+            },
+        )
+    }
+
+    pub fn mouse_motion(&mut self, pos: Vec2f) {
+        self.mouse_pos = pos;
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.stroke = None;
+    }
+
+    /// Applies the stroke in progress where the mouse is. Called every frame, so the brush
+    /// keeps working and dragged agents stay under the mouse while the world runs.
+    /// Changes are made as edits, so they survive time travel.
+    pub fn update(&mut self, params: &Params, view: &View, world: &mut World) {
+        let stroke = match self.stroke {
+            Some(stroke) => stroke,
+            None => return,
+        };
+        let to = window_to_world_pos(view, self.mouse_pos);
+        if let Stroke::Drag(id) = stroke {
+            world.edit(params, Edit::Move { id, pos: to });
+            self.stroke_pos = to;
+            return;
+        }
+
+        // Apply the brush all along the way the mouse moved, so fast strokes leave no gaps.
+        let from = self.stroke_pos;
+        let step = self.brush_radius / 2.0;
+        let steps = ((to - from).magnitude() / step).ceil().max(1.0) as u32;
+        let radius = self.brush_radius;
+        for idx in 1..=steps {
+            let center = Lerp::lerp(from, to, idx as f32 / steps as f32);
+            let edit = match stroke {
+                Stroke::Paint => Edit::Paint {
+                    species: self.species,
+                    center,
+                    radius,
+                },
+                Stroke::Erase => Edit::Erase { center, radius },
+                Stroke::PlaceWalls => Edit::SetWalls {
+                    center,
+                    radius,
+                    wall: true,
+                },
+                Stroke::RemoveWalls => Edit::SetWalls {
+                    center,
+                    radius,
+                    wall: false,
+                },
+                Stroke::Drag(_) => return,
+            };
+            world.edit(params, edit);
+        }
+        self.stroke_pos = to;
+    }
+
+    /// Describes the tool in use and why the last click did nothing, or returns `None` while
+    /// agents are selected as usual.
+    pub fn describe(&self) -> Option<String> {
+        let tool = match self.tool {
+            Tool::Select => return None,
+            Tool::Plants => format!(
+                "tool plants ({}), brush {}",
+                self.species.as_ref().to_lowercase(),
+                self.brush_radius
+            ),
+            Tool::Agents => "tool agents".to_string(),
+            Tool::Walls => format!("tool walls, brush {}", self.brush_radius),
+        };
+        match &self.message {
+            Some(message) => Some(format!("{}: {}", tool, message)),
+            None => Some(tool),
+        }
+    }
+
+    /// Outlines the brush around the mouse, if the tool has one.
+    pub fn draw(&self, target: &mut impl DrawTarget, view: &View) {
+        if self.tool != Tool::Plants && self.tool != Tool::Walls {
+            return;
+        }
+        let radius = self.brush_radius * view.zoom;
+        let point = |idx: u32| {
+            let angle = idx as f32 / BRUSH_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+            vec2f_to_vec2i(self.mouse_pos + calculate_vec2f(radius, angle))
+        };
+        for idx in 0..BRUSH_SEGMENTS {
+            target.draw_line(point(idx), point(idx + 1), BRUSH_COLOR);
+        }
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{Size2i, Vec2i};
+
+    #[test]
+    fn strokes_leave_no_gaps() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        let view = View::new(Size2i::new(800, 600), Vec2f::new(100.0, 100.0));
+        let mut editor = Editor::new();
+        editor.next_tool();
+        assert_eq!(editor.tool, Tool::Plants);
+
+        // Erase from the center of the window to 10 cells to the right in a single frame.
+        let start = Vec2f::new(400.0, 300.0);
+        editor.mouse_down(MouseButton::Right, false, start, &params, &view, &mut world);
+        editor.mouse_motion(start + Vec2f::new(10.0 * view.zoom, 0.0));
+        editor.update(&params, &view, &mut world);
+        editor.mouse_up();
+        for x in 100..110 {
+            for species in PlantSpecies::iter() {
+                let cell = Vec2i::new(x, 100);
+                assert_eq!(world.plant_grid.get_density_unchecked(species, cell), 0);
+            }
+        }
+
+        editor.next_tool();
+        editor.next_tool();
+        assert_eq!(editor.tool, Tool::Walls);
+        editor.mouse_down(MouseButton::Left, false, start, &params, &view, &mut world);
+        assert!(world.plant_grid.is_wall(Vec2i::new(100, 100)));
+        assert!(!world.plant_grid.is_wall(Vec2i::new(110, 100)));
+    }
+
+    #[test]
+    fn spawning_without_genomes_is_explained() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        let view = View::new(Size2i::new(800, 600), Vec2f::new(100.0, 100.0));
+        let mut editor = Editor::new();
+        editor.next_tool();
+        editor.next_tool();
+        assert_eq!(editor.tool, Tool::Agents);
+
+        let pos = Vec2f::new(10.0, 10.0);
+        assert!(editor
+            .mouse_down(MouseButton::Left, true, pos, &params, &view, &mut world)
+            .is_none());
+        assert_eq!(
+            editor.describe().unwrap(),
+            "tool agents: no genomes to spawn, start with genomes=<file>"
+        );
+
+        let id = editor.mouse_down(MouseButton::Left, false, pos, &params, &view, &mut world);
+        assert!(world.agent_by_id(id.unwrap()).is_some());
+        assert_eq!(editor.describe().unwrap(), "tool agents");
+    }
+}
@@ -0,0 +1,146 @@
+use crate::gfx::view::View;
+use crate::util::Vec2f;
+use crate::world::{Topology, World};
+use std::collections::HashSet;
+
+/// Keeps the camera centred on an agent, and optionally on its descendants after it dies.
+pub struct Follow {
+    /// The id of the agent the camera is centred on.
+    pub agent: u64,
+    /// Whether to switch to a descendant when the agent dies.
+    pub descendants: bool,
+    /// The followed agent and all of its living descendants.
+    family: HashSet<u64>,
+    /// Where the followed agent was last seen.
+    last_pos: Vec2f,
+}
+
+/// Returns the shortest displacement from one position to another.
+/// On a torus this may cross the border of the world.
+fn displacement(world: &World, from: Vec2f, to: Vec2f) -> Vec2f {
+    let delta = to - from;
+    match world.plant_grid.topology {
+        Topology::Walled => delta,
+        Topology::Torus => {
+            let size = Vec2f::new(
+                world.plant_grid.size.w as f32,
+                world.plant_grid.size.h as f32,
+            );
+            delta - (delta / size).round() * size
+        }
+    }
+}
+
+impl Follow {
+    /// Starts following a living agent.
+    pub fn new(world: &World, agent: u64, descendants: bool) -> Follow {
+        let mut family = HashSet::new();
+        family.insert(agent);
+        Follow {
+            agent,
+            descendants,
+            family,
+            last_pos: world
+                .agent_by_id(agent)
+                .map_or(Vec2f::zero(), |agent| agent.pos),
+        }
+    }
+
+    /// Keeps track of the descendants of the followed agent, and switches to the descendant
+    /// closest to it once it has died. Must be called after every tick, or descendants whose
+    /// parents died within the same ticks are missed.
+    /// Returns false if there is no agent left to follow.
+    pub fn update(&mut self, world: &World) -> bool {
+        let family = &self.family;
+        self.family = world
+            .agents
+            .iter()
+            .filter(|agent| {
+                family.contains(&agent.id)
+                    || matches!(agent.parent, Some(parent) if family.contains(&parent))
+            })
+            .map(|agent| agent.id)
+            .collect();
+
+        if let Some(agent) = world.agent_by_id(self.agent) {
+            self.last_pos = agent.pos;
+            return true;
+        }
+        if !self.descendants {
+            return false;
+        }
+
+        let last_pos = self.last_pos;
+        let closest = world
+            .agents
+            .iter()
+            .filter(|agent| self.family.contains(&agent.id))
+            .min_by(|a, b| {
+                let distance_a = displacement(world, last_pos, a.pos).magnitude_squared();
+                let distance_b = displacement(world, last_pos, b.pos).magnitude_squared();
+                distance_a.partial_cmp(&distance_b).unwrap()
+            });
+        match closest {
+            Some(agent) => {
+                self.agent = agent.id;
+                self.last_pos = agent.pos;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the camera smoothly towards the followed agent. On a torus the camera takes
+    /// the shortest way across the border, and is kept inside the world.
+    pub fn move_camera(&self, view: &mut View, world: &World, d_time: f32) {
+        let target = view.pos + displacement(world, view.pos, self.last_pos);
+        view.move_towards(target, d_time);
+        if world.plant_grid.topology == Topology::Torus {
+            view.pos = world.plant_grid.wrap_pos(view.pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Size2i;
+    use crate::world::Params;
+
+    #[test]
+    fn descendants_are_followed_after_death() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        let parent = world.agents[1].id;
+        let mut follow = Follow::new(&world, parent, true);
+        let mut other = Follow::new(&world, parent, false);
+
+        world.agents[1].time_since_reproduce = 1000.0;
+        world.tick(&params, params.tick_interval);
+        assert!(follow.update(&world));
+        assert!(other.update(&world));
+        let child = world.agents.last().unwrap().id;
+        assert_eq!(world.agents.last().unwrap().parent, Some(parent));
+
+        world.agents[1].energy = -1.0;
+        world.tick(&params, params.tick_interval);
+        assert!(follow.update(&world));
+        assert_eq!(follow.agent, child);
+        assert!(!other.update(&world));
+    }
+
+    #[test]
+    fn camera_crosses_the_border_of_a_torus() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        world.agents[0].pos = Vec2f::new(1.0, 100.0);
+        let mut follow = Follow::new(&world, world.agents[0].id, false);
+        assert!(follow.update(&world));
+        let mut view = View::new(Size2i::new(400, 400), Vec2f::new(198.0, 100.0));
+
+        for _ in 0..100 {
+            follow.move_camera(&mut view, &world, 0.1);
+        }
+        assert!((view.pos - Vec2f::new(1.0, 100.0)).magnitude() < 0.01);
+    }
+}
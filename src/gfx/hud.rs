@@ -0,0 +1,120 @@
+use crate::gfx::draw_target::DrawTarget;
+use crate::gfx::editor::Editor;
+use crate::gfx::font::{draw_text, text_size};
+use crate::gfx::panel::{draw_panel, PADDING, TEXT_COLOR, TEXT_SCALE};
+use crate::gfx::view::View;
+use crate::util::{Rect2i, Size2i};
+use crate::world::World;
+
+/// How often the frame rate is measured, in seconds.
+const FPS_INTERVAL: f32 = 0.5;
+
+/// Measures the number of frames drawn per second, averaged over a short interval so the
+/// number stays readable.
+pub struct FpsCounter {
+    frames: u32,
+    elapsed: f32,
+    fps: f32,
+}
+
+impl FpsCounter {
+    pub fn new() -> FpsCounter {
+        FpsCounter {
+            frames: 0,
+            elapsed: 0.0,
+            fps: 0.0,
+        }
+    }
+
+    /// Counts a frame that took `d_time` real seconds.
+    pub fn update(&mut self, d_time: f32) {
+        self.frames += 1;
+        self.elapsed += d_time;
+        if self.elapsed >= FPS_INTERVAL {
+            self.fps = self.frames as f32 / self.elapsed;
+            self.frames = 0;
+            self.elapsed = 0.0;
+        }
+    }
+
+    /// The frame rate of the last complete interval.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+}
+
+impl Default for FpsCounter {
+    fn default() -> FpsCounter {
+        FpsCounter::new()
+    }
+}
+
+/// Describes the state of the simulation: its time and speed, seed and population, and the
+/// editing tool in use.
+fn describe(view: &View, world: &World, fps: f32, editor: &Editor) -> String {
+    let (founders, immigrants) = world.population_by_origin();
+    let mut time = format!("time {:.1} s, speed x{}", world.time, view.time_factor);
+    if view.paused {
+        time += " (paused)";
+    }
+    let mut lines = vec![
+        time,
+        format!("seed {}", world.seed),
+        format!(
+            "agents {} (founders {}, immigrants {})",
+            world.agents.len(),
+            founders,
+            immigrants
+        ),
+        format!("max generation {}", world.statistics.max_generation),
+        format!("fps {:.0}", fps),
+    ];
+    lines.extend(editor.describe());
+    lines.join("\n")
+}
+
+/// Draws a panel with the state of the simulation in the bottom right corner of the window.
+pub fn draw_hud(
+    target: &mut impl DrawTarget,
+    view: &View,
+    world: &World,
+    fps: f32,
+    editor: &Editor,
+) {
+    let text = describe(view, world, fps, editor);
+    let size = text_size(&text, TEXT_SCALE);
+    let panel_size = Size2i::new(size.w + PADDING * 2, size.h + PADDING * 2);
+    let panel_rect = Rect2i::new(
+        view.window_size.w as i32 - panel_size.w as i32,
+        view.window_size.h as i32 - panel_size.h as i32,
+        panel_size.w,
+        panel_size.h,
+    );
+    draw_panel(target, panel_rect);
+    draw_text(
+        target,
+        panel_rect.x + PADDING as i32,
+        panel_rect.y + PADDING as i32,
+        &text,
+        TEXT_COLOR,
+        TEXT_SCALE,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fps_is_averaged_over_the_interval() {
+        let mut counter = FpsCounter::new();
+        for _ in 0..10 {
+            counter.update(0.04);
+        }
+        assert_eq!(counter.fps(), 0.0);
+        for _ in 0..3 {
+            counter.update(0.04);
+        }
+        assert!((counter.fps() - 25.0).abs() < 0.01);
+    }
+}
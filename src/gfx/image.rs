@@ -0,0 +1,391 @@
+use crate::gfx::assets::{asset_path, AGENT_EYES_SPRITE_PATH, AGENT_SPRITE_PATH};
+use crate::gfx::draw_target::{DrawTarget, Sprite};
+use crate::gfx::overlay::{CellColors, Overlay};
+use crate::gfx::view::View;
+use crate::gfx::world::{draw_world, PlantGridImage};
+use crate::util::{Rect2i, Size2i, Vec2f, Vec2i};
+use crate::world::World;
+use sdl2::pixels::Color;
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::Path;
+
+/// An RGBA image in memory, with 8 bits per channel.
+#[derive(Clone, PartialEq)]
+pub struct Image {
+    pub size: Size2i,
+    /// The channels of every pixel, row by row from the top.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Creates a transparent black image.
+    pub fn new(size: Size2i) -> Image {
+        Image {
+            size,
+            pixels: vec![0; size.w as usize * size.h as usize * 4],
+        }
+    }
+
+    /// Loads an RGB or RGBA PNG file.
+    pub fn load_png(path: &Path) -> Result<Image, String> {
+        let path_err = |err: &dyn std::fmt::Display| format!("{}: {}", path.display(), err);
+        let file = File::open(path).map_err(|err| path_err(&err))?;
+        let (info, mut reader) = png::Decoder::new(file)
+            .read_info()
+            .map_err(|err| path_err(&err))?;
+        let mut buffer = vec![0; info.buffer_size()];
+        reader
+            .next_frame(&mut buffer)
+            .map_err(|err| path_err(&err))?;
+
+        let pixels = match (info.color_type, info.bit_depth) {
+            (png::ColorType::RGBA, png::BitDepth::Eight) => buffer,
+            (png::ColorType::RGB, png::BitDepth::Eight) => buffer
+                .chunks(3)
+                .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+            _ => return Err(path_err(&"only 8-bit RGB and RGBA images are supported")),
+        };
+
+        Ok(Image {
+            size: Size2i::new(info.width, info.height),
+            pixels,
+        })
+    }
+
+    /// Saves the image to a PNG file.
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        let path_err = |err: &dyn std::fmt::Display| format!("{}: {}", path, err);
+        let file = File::create(path).map_err(|err| path_err(&err))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.size.w, self.size.h);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(|err| path_err(&err))
+    }
+
+    /// Returns the channels of a pixel.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let idx = (y as usize * self.size.w as usize + x as usize) * 4;
+        [
+            self.pixels[idx],
+            self.pixels[idx + 1],
+            self.pixels[idx + 2],
+            self.pixels[idx + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        let idx = (y as usize * self.size.w as usize + x as usize) * 4;
+        self.pixels[idx..idx + 4].copy_from_slice(&color);
+    }
+
+    /// Draws a color over a pixel, according to the alpha of the color.
+    fn blend_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        let idx = (y as usize * self.size.w as usize + x as usize) * 4;
+        let alpha = color[3] as u32;
+        for (dst, src) in self.pixels[idx..idx + 3].iter_mut().zip(color.iter()) {
+            *dst = ((*src as u32 * alpha + *dst as u32 * (255 - alpha)) / 255) as u8;
+        }
+        let dst_alpha = self.pixels[idx + 3] as u32;
+        self.pixels[idx + 3] = (alpha + dst_alpha * (255 - alpha) / 255) as u8;
+    }
+
+    /// Returns the part of a rectangle that lies within the image, as pixel ranges.
+    fn clip(&self, rect: Rect2i) -> (std::ops::Range<u32>, std::ops::Range<u32>) {
+        let clamp = |value: i64, max: u32| value.max(0).min(max as i64) as u32;
+        let cols =
+            clamp(rect.x as i64, self.size.w)..clamp(rect.x as i64 + rect.w as i64, self.size.w);
+        let rows =
+            clamp(rect.y as i64, self.size.h)..clamp(rect.y as i64 + rect.h as i64, self.size.h);
+        (cols, rows)
+    }
+}
+
+/// The sprites for software rendering.
+pub struct Sprites {
+    agent: Image,
+    agent_eyes: Image,
+}
+
+impl Sprites {
+    /// Load the sprites from disk.
+    pub fn load() -> Result<Sprites, String> {
+        Ok(Sprites {
+            agent: Image::load_png(&asset_path(AGENT_SPRITE_PATH))?,
+            agent_eyes: Image::load_png(&asset_path(AGENT_EYES_SPRITE_PATH))?,
+        })
+    }
+
+    /// White agents without eyes, so tests don't depend on the asset files.
+    #[cfg(test)]
+    pub fn plain() -> Sprites {
+        Sprites {
+            agent: Image {
+                size: Size2i::new(4, 4),
+                pixels: vec![255; 4 * 4 * 4],
+            },
+            agent_eyes: Image::new(Size2i::new(4, 4)),
+        }
+    }
+}
+
+/// Draws on an image in memory, the same way as on a window.
+pub struct ImageTarget<'a> {
+    image: &'a mut Image,
+    sprites: &'a Sprites,
+}
+
+impl<'a> ImageTarget<'a> {
+    pub fn new(image: &'a mut Image, sprites: &'a Sprites) -> ImageTarget<'a> {
+        ImageTarget { image, sprites }
+    }
+}
+
+impl DrawTarget for ImageTarget<'_> {
+    fn clear(&mut self, color: Color) {
+        for pixel in self.image.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    fn fill_rect(&mut self, rect: Rect2i, color: Color) {
+        let (cols, rows) = self.image.clip(rect);
+        for y in rows {
+            for x in cols.clone() {
+                self.image.set_pixel(x, y, [color.r, color.g, color.b, 255]);
+            }
+        }
+    }
+
+    fn draw_rect(&mut self, rect: Rect2i, color: Color) {
+        if rect.w == 0 || rect.h == 0 {
+            return;
+        }
+        let right = rect.x + rect.w as i32 - 1;
+        let bottom = rect.y + rect.h as i32 - 1;
+        self.fill_rect(Rect2i::new(rect.x, rect.y, rect.w, 1), color);
+        self.fill_rect(Rect2i::new(rect.x, bottom, rect.w, 1), color);
+        self.fill_rect(Rect2i::new(rect.x, rect.y, 1, rect.h), color);
+        self.fill_rect(Rect2i::new(right, rect.y, 1, rect.h), color);
+    }
+
+    fn draw_line(&mut self, from: Vec2i, to: Vec2i, color: Color) {
+        // Bresenham's line algorithm, which works in every direction.
+        let delta = Vec2i::new((to.x - from.x).abs(), -(to.y - from.y).abs());
+        let step = Vec2i::new((to.x - from.x).signum(), (to.y - from.y).signum());
+        let mut error = delta.x + delta.y;
+        let mut pos = from;
+        loop {
+            self.fill_rect(Rect2i::new(pos.x, pos.y, 1, 1), color);
+            if pos == to {
+                break;
+            }
+            let double_error = error * 2;
+            if double_error >= delta.y {
+                error += delta.y;
+                pos.x += step.x;
+            }
+            if double_error <= delta.x {
+                error += delta.x;
+                pos.y += step.y;
+            }
+        }
+    }
+
+    fn draw_plant_grid(&mut self, image: &Image, _changed_rows: Range<u32>, rects: &[Rect2i]) {
+        // Sample the image at every pixel, like a texture without filtering.
+        let source_pos = |pos: u32, start: i32, length: u32, source_length: u32| {
+            ((pos as i64 - start as i64) * source_length as i64 / length as i64) as u32
+        };
+        for rect in rects.iter() {
+            let (cols, rows) = self.image.clip(*rect);
+            for y in rows {
+                let source_y = source_pos(y, rect.y, rect.h, image.size.h);
+                for x in cols.clone() {
+                    let source_x = source_pos(x, rect.x, rect.w, image.size.w);
+                    self.image.set_pixel(x, y, image.pixel(source_x, source_y));
+                }
+            }
+        }
+    }
+
+    fn draw_sprite(&mut self, sprite: Sprite, rect: Rect2i, angle: f64, color_mod: [u8; 3]) {
+        if rect.w == 0 || rect.h == 0 {
+            return;
+        }
+        let sprite = match sprite {
+            Sprite::Agent => &self.sprites.agent,
+            Sprite::AgentEyes => &self.sprites.agent_eyes,
+        };
+
+        // Visit every pixel the rotated rectangle might cover, and rotate it back into the sprite.
+        let pivot = Vec2f::new((rect.w / 2) as f32, (rect.h / 2) as f32);
+        let center = Vec2f::new(rect.x as f32, rect.y as f32) + pivot;
+        let radius = ((rect.w * rect.w + rect.h * rect.h) as f32).sqrt() / 2.0;
+        let bounds = Rect2i::new(
+            (center.x - radius).floor() as i32,
+            (center.y - radius).floor() as i32,
+            (radius * 2.0).ceil() as u32 + 1,
+            (radius * 2.0).ceil() as u32 + 1,
+        );
+        let (sin, cos) = (angle as f32).to_radians().sin_cos();
+        let (cols, rows) = self.image.clip(bounds);
+        for y in rows {
+            for x in cols.clone() {
+                let offset = Vec2f::new(x as f32 + 0.5, y as f32 + 0.5) - center;
+                let local_x = offset.x * cos + offset.y * sin + pivot.x;
+                let local_y = -offset.x * sin + offset.y * cos + pivot.y;
+                if local_x < 0.0
+                    || local_y < 0.0
+                    || local_x >= rect.w as f32
+                    || local_y >= rect.h as f32
+                {
+                    continue;
+                }
+
+                let sprite_x = (local_x * sprite.size.w as f32 / rect.w as f32) as u32;
+                let sprite_y = (local_y * sprite.size.h as f32 / rect.h as f32) as u32;
+                let texel = sprite.pixel(
+                    sprite_x.min(sprite.size.w - 1),
+                    sprite_y.min(sprite.size.h - 1),
+                );
+                let color = [
+                    (texel[0] as u32 * color_mod[0] as u32 / 255) as u8,
+                    (texel[1] as u32 * color_mod[1] as u32 / 255) as u8,
+                    (texel[2] as u32 * color_mod[2] as u32 / 255) as u8,
+                    texel[3],
+                ];
+                self.image.blend_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Returns a view of the given size that shows the whole world.
+pub fn fit_view(size: Size2i, world: &World) -> View {
+    let mut view = View::new(
+        size,
+        Vec2f::new(
+            world.plant_grid.size.w as f32 / 2.0,
+            world.plant_grid.size.h as f32 / 2.0,
+        ),
+    );
+    view.zoom = (size.w as f32 / world.plant_grid.size.w as f32)
+        .min(size.h as f32 / world.plant_grid.size.h as f32);
+    view
+}
+
+/// Renders the world to an image without a window, exactly as it would be drawn on a window.
+pub fn render_world(view: &View, world: &World, sprites: &Sprites) -> Image {
+    let mut plant_grid_image = PlantGridImage::new();
+    plant_grid_image.update(world, &CellColors::new(Overlay::Plants, world));
+    let mut image = Image::new(view.window_size);
+    draw_world(
+        &mut ImageTarget::new(&mut image, sprites),
+        view,
+        world,
+        &plant_grid_image,
+    );
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::PlantSpecies;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn rects_are_clipped() {
+        let sprites = Sprites::plain();
+        let mut image = Image::new(Size2i::new(4, 3));
+        let mut target = ImageTarget::new(&mut image, &sprites);
+        target.fill_rect(Rect2i::new(-2, 1, 4, 10), Color::RGBA(10, 20, 30, 40));
+
+        assert_eq!(image.pixel(0, 0), [0, 0, 0, 0]);
+        assert_eq!(image.pixel(1, 1), [10, 20, 30, 255]);
+        assert_eq!(image.pixel(1, 2), [10, 20, 30, 255]);
+        assert_eq!(image.pixel(2, 1), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn lines_include_both_end_points() {
+        let sprites = Sprites::plain();
+        let mut image = Image::new(Size2i::new(10, 10));
+        let mut target = ImageTarget::new(&mut image, &sprites);
+        let color = Color::RGB(1, 2, 3);
+        target.draw_line(Vec2i::new(8, 1), Vec2i::new(2, 4), color);
+        target.draw_line(Vec2i::new(5, 12), Vec2i::new(5, 7), color);
+
+        assert_eq!(image.pixel(8, 1), [1, 2, 3, 255]);
+        assert_eq!(image.pixel(2, 4), [1, 2, 3, 255]);
+        assert_eq!(image.pixel(6, 2), [1, 2, 3, 255]);
+        assert_eq!(image.pixel(5, 7), [1, 2, 3, 255]);
+        assert_eq!(image.pixel(5, 9), [1, 2, 3, 255]);
+        assert_eq!(image.pixel(2, 1), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn sprites_are_rotated_and_colored() {
+        let sprites = Sprites::plain();
+        let mut image = Image::new(Size2i::new(20, 20));
+        let mut target = ImageTarget::new(&mut image, &sprites);
+        target.clear(Color::RGBA(0, 0, 0, 255));
+        target.draw_sprite(Sprite::Agent, Rect2i::new(5, 9, 10, 2), 90.0, [255, 0, 128]);
+
+        // A horizontal bar rotated by 90 degrees becomes a vertical bar.
+        assert_eq!(image.pixel(10, 6), [255, 0, 128, 255]);
+        assert_eq!(image.pixel(10, 13), [255, 0, 128, 255]);
+        assert_eq!(image.pixel(6, 10), [0, 0, 0, 255]);
+        assert_eq!(image.pixel(13, 10), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn rendered_world_shows_plants_and_agents() {
+        let world = World::new_seeded(&crate::world::Params::default(), 1);
+        let sprites = Sprites::plain();
+
+        let image = render_world(&fit_view(Size2i::new(400, 400), &world), &world, &sprites);
+        // Plant cells are two pixels wide, and the world is Y-up.
+        let pixel_at = |idx: usize| image.pixel(idx as u32 % 200 * 2, 399 - idx as u32 / 200 * 2);
+        let densities = world.plant_grid.densities(PlantSpecies::Grass);
+        let planted = densities.iter().position(|density| *density > 0).unwrap();
+        assert_ne!(pixel_at(planted), [0, 0, 0, 255]);
+        let empty = (0..densities.len())
+            .find(|idx| {
+                PlantSpecies::iter().all(|species| world.plant_grid.densities(species)[*idx] == 0)
+            })
+            .unwrap();
+        assert_eq!(pixel_at(empty), [0, 0, 0, 255]);
+
+        let agent = &world.agents[0];
+        let image = render_world(
+            &View::new(Size2i::new(400, 400), agent.pos),
+            &world,
+            &sprites,
+        );
+        let color = agent.genes.get_color() * 255.0;
+        assert_eq!(
+            image.pixel(200, 200),
+            [color.x as u8, color.y as u8, color.z as u8, 255]
+        );
+    }
+
+    #[test]
+    fn png_files_round_trip() {
+        let mut image = Image::new(Size2i::new(3, 2));
+        image.set_pixel(2, 1, [1, 2, 3, 4]);
+        let path = std::env::temp_dir().join("mini-universe-image-test.png");
+
+        image.save_png(path.to_str().unwrap()).unwrap();
+        let loaded = Image::load_png(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(loaded == image);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::gfx::draw_target::DrawTarget;
+use crate::gfx::font::{draw_text, text_size};
+use crate::gfx::panel::{draw_panel, PADDING, TEXT_COLOR, TEXT_SCALE};
+use crate::util::Rect2i;
+use crate::world::agent::{Command, Percept};
+use crate::world::{Agent, Gene, Origin, World};
+use strum::IntoEnumIterator;
+
+/// Describes an agent: who it is, its genes and what it currently senses and does.
+fn describe(agent: &Agent, world: &World) -> String {
+    let origin = match agent.origin {
+        Origin::Founder => "founder",
+        Origin::Immigrant => "immigrant",
+    };
+    let mut lines = vec![
+        format!("agent {}, lineage {} ({})", agent.id, agent.lineage, origin),
+        format!(
+            "generation {}, age {:.1} s",
+            agent.generation, agent.time_alive
+        ),
+        format!("energy {:.3}", agent.energy),
+        "genes".to_string(),
+    ];
+    for gene in Gene::iter() {
+        lines.push(format!(
+            " {:<18}{:>7.3}",
+            gene.as_ref(),
+            agent.genes.get(gene)
+        ));
+    }
+
+    let (percepts, commands) = agent.percepts_and_commands(&world.plant_grid);
+    lines.push("percepts".to_string());
+    for percept in Percept::iter() {
+        lines.push(format!(
+            " {:<18}{:>7.3}",
+            percept.as_ref(),
+            percepts[percept as usize]
+        ));
+    }
+    lines.push("commands".to_string());
+    for command in Command::iter() {
+        lines.push(format!(
+            " {:<18}{:>7.3}",
+            command.as_ref(),
+            commands[command as usize]
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Draws a panel with the details of an agent in the top left corner of the window.
+pub fn draw_inspector(target: &mut impl DrawTarget, agent: &Agent, world: &World) {
+    let text = describe(agent, world);
+    let size = text_size(&text, TEXT_SCALE);
+    let panel_rect = Rect2i::new(0, 0, size.w + PADDING * 2, size.h + PADDING * 2);
+    draw_panel(target, panel_rect);
+    draw_text(
+        target,
+        PADDING as i32,
+        PADDING as i32,
+        &text,
+        TEXT_COLOR,
+        TEXT_SCALE,
+    );
+}
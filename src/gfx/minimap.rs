@@ -0,0 +1,218 @@
+use crate::gfx::draw_target::DrawTarget;
+use crate::gfx::overlay::CellColors;
+use crate::gfx::panel::BORDER_COLOR;
+use crate::gfx::view::View;
+use crate::gfx::world::visible_world_rect;
+use crate::util::{Rect2i, Vec2f, Vec2i};
+use crate::world::{Topology, World};
+use sdl2::pixels::Color;
+
+const VIEW_COLOR: Color = Color::RGBA(255, 255, 255, 255);
+/// The most blocks of cells the minimap shows in either direction.
+const MAX_BLOCKS: u32 = 80;
+/// The size of a block of cells on the minimap, in pixels.
+const BLOCK_PIXELS: u32 = 2;
+const AGENT_PIXELS: u32 = 2;
+
+/// Where the minimap is drawn and how many cells every pixel shows.
+struct Layout {
+    rect: Rect2i,
+    /// The size of the square blocks of cells that are drawn as one block of pixels.
+    cells_per_block: u32,
+}
+
+impl Layout {
+    /// The minimap is in the top right corner of the window.
+    fn new(view: &View, world: &World) -> Layout {
+        let size = world.plant_grid.size;
+        let cells_per_block = (size.w.max(size.h) - 1) / MAX_BLOCKS + 1;
+        let blocks_w = (size.w - 1) / cells_per_block + 1;
+        let blocks_h = (size.h - 1) / cells_per_block + 1;
+        let (width, height) = (blocks_w * BLOCK_PIXELS, blocks_h * BLOCK_PIXELS);
+        Layout {
+            rect: Rect2i::new(view.window_size.w as i32 - width as i32, 0, width, height),
+            cells_per_block,
+        }
+    }
+
+    /// The number of pixels per world unit.
+    fn scale(&self) -> f32 {
+        BLOCK_PIXELS as f32 / self.cells_per_block as f32
+    }
+
+    /// Translates a position in the world to a position on the window.
+    fn world_to_window_pos(&self, pos: Vec2f) -> Vec2f {
+        // The world is Y-up but the window is Y-down, so flip it.
+        Vec2f::new(
+            self.rect.x as f32 + pos.x * self.scale(),
+            (self.rect.y + self.rect.h as i32) as f32 - pos.y * self.scale(),
+        )
+    }
+}
+
+/// Draws the whole world at a low resolution in the top right corner of the window: the
+/// plant grid in the colors of the overlay, the agents and the part the view shows.
+pub fn draw_minimap(
+    target: &mut impl DrawTarget,
+    view: &View,
+    world: &World,
+    cell_colors: &CellColors,
+) {
+    let layout = Layout::new(view, world);
+    let size = world.plant_grid.size;
+    let cells_per_block = layout.cells_per_block;
+
+    for block_y in 0..layout.rect.h / BLOCK_PIXELS {
+        for block_x in 0..layout.rect.w / BLOCK_PIXELS {
+            // Show the brightest color of every channel, so sparse plants stay visible.
+            let mut rgb = [0u8; 3];
+            for y in block_y * cells_per_block..((block_y + 1) * cells_per_block).min(size.h) {
+                for x in block_x * cells_per_block..((block_x + 1) * cells_per_block).min(size.w) {
+                    if let Some(color) = cell_colors.color(Vec2i::new(x as i32, y as i32)) {
+                        rgb[0] = rgb[0].max(color.r);
+                        rgb[1] = rgb[1].max(color.g);
+                        rgb[2] = rgb[2].max(color.b);
+                    }
+                }
+            }
+            let pos = layout.world_to_window_pos(Vec2f::new(
+                (block_x * cells_per_block) as f32,
+                ((block_y + 1) * cells_per_block) as f32,
+            ));
+            target.fill_rect(
+                Rect2i::new(pos.x as i32, pos.y as i32, BLOCK_PIXELS, BLOCK_PIXELS),
+                Color::RGBA(rgb[0], rgb[1], rgb[2], 255),
+            );
+        }
+    }
+
+    for agent in world.agents.iter() {
+        let pos = layout.world_to_window_pos(agent.pos);
+        let color = agent.genes.get_color();
+        target.fill_rect(
+            Rect2i::new(
+                pos.x as i32 - AGENT_PIXELS as i32 / 2,
+                pos.y as i32 - AGENT_PIXELS as i32 / 2,
+                AGENT_PIXELS,
+                AGENT_PIXELS,
+            ),
+            Color::RGBA(
+                (color.x * 255.0) as u8,
+                (color.y * 255.0) as u8,
+                (color.z * 255.0) as u8,
+                255,
+            ),
+        );
+    }
+
+    draw_view_rect(target, view, world, &layout);
+    target.draw_rect(layout.rect, BORDER_COLOR);
+}
+
+/// Outlines the part of the world the view shows, leaving out the edges beyond the minimap.
+/// On a torus the view may cross the border of the world, so it wraps around the minimap.
+fn draw_view_rect(target: &mut impl DrawTarget, view: &View, world: &World, layout: &Layout) {
+    let mut visible_rect = visible_world_rect(view);
+    let world_size = Vec2f::new(
+        world.plant_grid.size.w as f32,
+        world.plant_grid.size.h as f32,
+    );
+    let offsets = match world.plant_grid.topology {
+        Topology::Walled => vec![Vec2f::zero()],
+        Topology::Torus => {
+            let wrapped = world
+                .plant_grid
+                .wrap_pos(Vec2f::new(visible_rect.x, visible_rect.y));
+            visible_rect.x = wrapped.x;
+            visible_rect.y = wrapped.y;
+            vec![
+                Vec2f::zero(),
+                Vec2f::new(-world_size.x, 0.0),
+                Vec2f::new(0.0, -world_size.y),
+                -world_size,
+            ]
+        }
+    };
+
+    let bounds = layout.rect;
+    let (left, right) = (bounds.x, bounds.x + bounds.w as i32 - 1);
+    let (top, bottom) = (bounds.y, bounds.y + bounds.h as i32 - 1);
+    for offset in offsets {
+        let top_left = layout.world_to_window_pos(
+            Vec2f::new(visible_rect.x, visible_rect.y + visible_rect.h) + offset,
+        );
+        let bottom_right = layout.world_to_window_pos(
+            Vec2f::new(visible_rect.x + visible_rect.w, visible_rect.y) + offset,
+        );
+        let (x0, y0) = (top_left.x as i32, top_left.y as i32);
+        let (x1, y1) = (bottom_right.x as i32, bottom_right.y as i32);
+        if x1 < left || x0 > right || y1 < top || y0 > bottom {
+            continue;
+        }
+
+        let (clipped_x0, clipped_x1) = (x0.max(left), x1.min(right));
+        let (clipped_y0, clipped_y1) = (y0.max(top), y1.min(bottom));
+        for y in [y0, y1].iter().filter(|y| (top..=bottom).contains(*y)) {
+            target.draw_line(
+                Vec2i::new(clipped_x0, *y),
+                Vec2i::new(clipped_x1, *y),
+                VIEW_COLOR,
+            );
+        }
+        for x in [x0, x1].iter().filter(|x| (left..=right).contains(*x)) {
+            target.draw_line(
+                Vec2i::new(*x, clipped_y0),
+                Vec2i::new(*x, clipped_y1),
+                VIEW_COLOR,
+            );
+        }
+    }
+}
+
+/// Returns the area of the window the minimap covers.
+pub fn minimap_rect(view: &View, world: &World) -> Rect2i {
+    Layout::new(view, world).rect
+}
+
+/// Returns the position in the world that a position on the window shows on the minimap,
+/// or `None` if the position is not on the minimap.
+pub fn minimap_to_world_pos(view: &View, world: &World, pos: Vec2f) -> Option<Vec2f> {
+    let layout = Layout::new(view, world);
+    let rect = layout.rect;
+    let world_pos = Vec2f::new(
+        (pos.x - rect.x as f32) / layout.scale(),
+        ((rect.y + rect.h as i32) as f32 - pos.y) / layout.scale(),
+    );
+    let size = world.plant_grid.size;
+    if pos.x < rect.x as f32 || pos.y < rect.y as f32 {
+        return None;
+    }
+    if world_pos.x >= size.w as f32 || world_pos.y < 0.0 || world_pos.y >= size.h as f32 {
+        return None;
+    }
+    Some(world_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Size2i;
+    use crate::world::Params;
+
+    #[test]
+    fn clicks_are_translated_to_the_world() {
+        let params = Params::default();
+        let world = World::new_seeded(&params, 1);
+        let view = View::new(Size2i::new(800, 600), Vec2f::zero());
+        let layout = Layout::new(&view, &world);
+        // 200 cells fit in 80 blocks of 3 cells, drawn 2 pixels wide.
+        assert_eq!(layout.cells_per_block, 3);
+        assert_eq!(layout.rect, Rect2i::new(800 - 134, 0, 134, 134));
+
+        let pos = Vec2f::new(30.0, 150.0);
+        let window_pos = layout.world_to_window_pos(pos);
+        let world_pos = minimap_to_world_pos(&view, &world, window_pos).unwrap();
+        assert!((world_pos - pos).magnitude() < 0.01);
+        assert!(minimap_to_world_pos(&view, &world, Vec2f::new(10.0, 10.0)).is_none());
+    }
+}
@@ -1,4 +1,18 @@
 mod assets;
-mod view;
+mod brain;
+mod charts;
+mod draw_target;
+mod editor;
+mod follow;
+mod font;
+mod hud;
+pub mod image;
+mod inspector;
+mod minimap;
+mod overlay;
+mod panel;
+pub mod recording;
+mod time_travel;
+pub mod view;
 pub mod window;
 mod world;
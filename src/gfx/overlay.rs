@@ -0,0 +1,312 @@
+use crate::gfx::draw_target::DrawTarget;
+use crate::gfx::font::{char_size, draw_text, text_size};
+use crate::gfx::panel::{draw_panel, PADDING, TEXT_COLOR, TEXT_SCALE};
+use crate::gfx::view::View;
+use crate::util::{Rect2i, Size2i, Vec2i};
+use crate::world::{PlantSpecies, World};
+use sdl2::pixels::Color;
+use strum::IntoEnumIterator;
+use strum_macros::{AsRefStr, EnumIter};
+use vek::ops::Clamp;
+
+pub const WALL_COLOR: Color = Color::RGBA(128, 128, 128, 255);
+const GRADIENT_WIDTH: u32 = 200;
+const GRADIENT_HEIGHT: u32 = 12;
+/// Agents are counted in square blocks of this many cells, because single cells are too sparse.
+const AGENT_BLOCK_SIZE: u32 = 8;
+/// Simulated seconds after which grazed cells are no longer highlighted.
+const GRAZED_FADE_TIME: f64 = 100.0;
+/// Simulated seconds after which territories fade out.
+const TERRITORY_FADE_TIME: f64 = 300.0;
+
+/// What the cells of the plant grid show.
+#[derive(Copy, Clone, PartialEq, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Overlay {
+    /// The plants in the colors of their species.
+    Plants,
+    /// The density of all plant species together.
+    PlantDensity,
+    /// The number of agents nearby.
+    AgentDensity,
+    /// The energy agents gained by eating in every cell.
+    EnergyEaten,
+    /// How long ago agents last ate in every cell.
+    TimeSinceGrazed,
+    /// Which lineage last ate in every cell.
+    LineageTerritory,
+}
+
+impl Overlay {
+    /// Returns whether the colors of a cell only change when its plants or walls change,
+    /// and not as time passes or agents move.
+    pub fn shows_only_plants(self) -> bool {
+        matches!(self, Overlay::Plants | Overlay::PlantDensity)
+    }
+
+    /// Returns the next overlay, starting over after the last one.
+    pub fn next(self) -> Overlay {
+        let mut overlays = Overlay::iter().cycle();
+        overlays.find(|overlay| *overlay == self);
+        overlays.next().unwrap()
+    }
+}
+
+/// Maps a value from 0 to 1 to a color going from black through red and yellow to white.
+fn heat_color(value: f32) -> Color {
+    let value = value.clamped(0.0, 1.0) * 3.0;
+    let channel = |start: f32| ((value - start).clamped(0.0, 1.0) * 255.0) as u8;
+    Color::RGBA(channel(0.0), channel(1.0), channel(2.0), 255)
+}
+
+/// Returns a distinct, stable color for every lineage.
+fn lineage_color(lineage: u32, brightness: f32) -> Color {
+    // Successive lineages are spread around the color wheel by the golden angle.
+    let hue = (lineage as f32 * 0.618_034).fract() * 6.0;
+    let channel = |offset: f32| {
+        let distance = ((hue - offset + 9.0) % 6.0 - 3.0).abs();
+        ((distance - 1.0).clamped(0.0, 1.0) * 255.0 * brightness) as u8
+    };
+    Color::RGBA(channel(3.0), channel(1.0), channel(5.0), 255)
+}
+
+/// Colors the cells of the plant grid for an overlay. Everything that depends on the whole
+/// grid, such as the highest value, is computed once per frame.
+pub struct CellColors<'a> {
+    overlay: Overlay,
+    world: &'a World,
+    /// The number of agents in every block of cells, row by row.
+    agent_counts: Vec<u32>,
+    /// The highest value of the overlay, which is shown in the legend.
+    max: f32,
+    /// The lowest value of the overlay. Only the energy eaten can be negative.
+    min: f32,
+}
+
+impl<'a> CellColors<'a> {
+    pub fn new(overlay: Overlay, world: &'a World) -> CellColors<'a> {
+        let blocks = agent_blocks(world);
+        let mut agent_counts = Vec::new();
+        let (mut min, mut max) = (0.0, 1.0);
+        match overlay {
+            Overlay::AgentDensity => {
+                agent_counts = vec![0; blocks.w as usize * blocks.h as usize];
+                for agent in world.agents.iter() {
+                    if let Some(cell) = world.plant_grid.cell_at(agent.pos) {
+                        let block = cell / AGENT_BLOCK_SIZE as i32;
+                        agent_counts[block.y as usize * blocks.w as usize + block.x as usize] += 1;
+                    }
+                }
+                max = agent_counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+            }
+            Overlay::EnergyEaten => {
+                let energy_eaten = world.grazing.energy_eaten();
+                min = energy_eaten.iter().copied().fold(0.0, f32::min);
+                max = energy_eaten.iter().copied().fold(0.0, f32::max);
+            }
+            _ => {}
+        }
+
+        CellColors {
+            overlay,
+            world,
+            agent_counts,
+            max,
+            min,
+        }
+    }
+
+    pub fn overlay(&self) -> Overlay {
+        self.overlay
+    }
+
+    /// Returns the color of a cell, or `None` to leave it empty. Walls look the same in every
+    /// overlay.
+    pub fn color(&self, cell: Vec2i) -> Option<Color> {
+        let world = self.world;
+        if world.plant_grid.is_wall(cell) {
+            return Some(WALL_COLOR);
+        }
+        match self.overlay {
+            Overlay::Plants => {
+                // Blend the colors of all plant species in this cell additively.
+                let mut total_density = 0i32;
+                let mut rgb = [0i32; 3];
+                for species in PlantSpecies::iter() {
+                    let density = world.plant_grid.get_density_unchecked(species, cell) as i32;
+                    let species_color = species.traits().color;
+                    for channel in 0..3 {
+                        rgb[channel] += (species_color[channel] as i32 * density) / 255;
+                    }
+                    total_density += density;
+                }
+                if total_density == 0 {
+                    return None;
+                }
+                Some(Color::RGBA(
+                    rgb[0].min(255) as u8,
+                    rgb[1].min(255) as u8,
+                    rgb[2].min(255) as u8,
+                    255,
+                ))
+            }
+            Overlay::PlantDensity => {
+                let total_density: u32 = PlantSpecies::iter()
+                    .map(|species| world.plant_grid.get_density_unchecked(species, cell) as u32)
+                    .sum();
+                if total_density == 0 {
+                    return None;
+                }
+                Some(heat_color(total_density as f32 / 255.0))
+            }
+            Overlay::AgentDensity => {
+                let blocks = agent_blocks(world);
+                let block = cell / AGENT_BLOCK_SIZE as i32;
+                let count =
+                    self.agent_counts[block.y as usize * blocks.w as usize + block.x as usize];
+                if count == 0 {
+                    return None;
+                }
+                Some(heat_color(count as f32 / self.max))
+            }
+            Overlay::EnergyEaten => {
+                let idx = cell.y as usize * world.plant_grid.size.w as usize + cell.x as usize;
+                let energy = world.grazing.energy_eaten()[idx];
+                if energy > 0.0 {
+                    Some(heat_color(energy / self.max))
+                } else if energy < 0.0 {
+                    // Cells where agents lost energy to poison are purple.
+                    let brightness = (energy / self.min * 255.0) as u8;
+                    Some(Color::RGBA(brightness / 2, 0, brightness, 255))
+                } else {
+                    None
+                }
+            }
+            Overlay::TimeSinceGrazed => {
+                let time_since = world.time - world.grazing.last_grazed(cell)?;
+                if time_since >= GRAZED_FADE_TIME {
+                    return None;
+                }
+                Some(heat_color(1.0 - (time_since / GRAZED_FADE_TIME) as f32))
+            }
+            Overlay::LineageTerritory => {
+                let lineage = world.grazing.last_lineage(cell)?;
+                let time_since = world.time - world.grazing.last_grazed(cell)?;
+                if time_since >= TERRITORY_FADE_TIME {
+                    return None;
+                }
+                let brightness = 1.0 - (time_since / TERRITORY_FADE_TIME) as f32 * 0.75;
+                Some(lineage_color(lineage, brightness))
+            }
+        }
+    }
+
+    /// Draws a legend of the overlay at the top of the window, in the middle.
+    pub fn draw_legend(&self, target: &mut impl DrawTarget, view: &View) {
+        let (title, low, high) = match self.overlay {
+            Overlay::Plants => return,
+            Overlay::PlantDensity => (
+                "plant density".to_string(),
+                "0".to_string(),
+                "1".to_string(),
+            ),
+            Overlay::AgentDensity => (
+                format!("agents per {0}x{0} cells", AGENT_BLOCK_SIZE),
+                "0".to_string(),
+                format!("{}", self.max),
+            ),
+            Overlay::EnergyEaten => (
+                "energy eaten, poison in purple".to_string(),
+                "0".to_string(),
+                format!("{:.2}", self.max),
+            ),
+            Overlay::TimeSinceGrazed => (
+                "time since grazed".to_string(),
+                format!("{} s", GRAZED_FADE_TIME),
+                "0 s".to_string(),
+            ),
+            Overlay::LineageTerritory => (
+                "last lineage to graze".to_string(),
+                String::new(),
+                String::new(),
+            ),
+        };
+        let line_height = char_size(TEXT_SCALE).h;
+        let has_gradient = self.overlay != Overlay::LineageTerritory;
+        let content_size = Size2i::new(
+            text_size(&title, TEXT_SCALE).w.max(GRADIENT_WIDTH),
+            if has_gradient {
+                line_height * 2 + GRADIENT_HEIGHT
+            } else {
+                line_height
+            },
+        );
+        let panel_size = Size2i::new(content_size.w + PADDING * 2, content_size.h + PADDING * 2);
+        let panel_rect = Rect2i::new(
+            (view.window_size.w as i32 - panel_size.w as i32) / 2,
+            0,
+            panel_size.w,
+            panel_size.h,
+        );
+        draw_panel(target, panel_rect);
+        let content_x = panel_rect.x + PADDING as i32;
+        let content_y = panel_rect.y + PADDING as i32;
+        draw_text(target, content_x, content_y, &title, TEXT_COLOR, TEXT_SCALE);
+        if !has_gradient {
+            return;
+        }
+
+        let gradient_x = content_x + (content_size.w - GRADIENT_WIDTH) as i32 / 2;
+        let gradient_y = content_y + line_height as i32;
+        for x in 0..GRADIENT_WIDTH {
+            let value = x as f32 / (GRADIENT_WIDTH - 1) as f32;
+            target.fill_rect(
+                Rect2i::new(gradient_x + x as i32, gradient_y, 1, GRADIENT_HEIGHT),
+                heat_color(value),
+            );
+        }
+        let labels_y = gradient_y + GRADIENT_HEIGHT as i32;
+        draw_text(target, gradient_x, labels_y, &low, TEXT_COLOR, TEXT_SCALE);
+        let high_width = text_size(&high, TEXT_SCALE).w as i32;
+        draw_text(
+            target,
+            gradient_x + GRADIENT_WIDTH as i32 - high_width,
+            labels_y,
+            &high,
+            TEXT_COLOR,
+            TEXT_SCALE,
+        );
+    }
+}
+
+/// Returns the number of blocks in which agents are counted. The last blocks may be partial.
+fn agent_blocks(world: &World) -> Size2i {
+    let size = world.plant_grid.size;
+    Size2i::new(
+        (size.w - 1) / AGENT_BLOCK_SIZE + 1,
+        (size.h - 1) / AGENT_BLOCK_SIZE + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Params;
+
+    #[test]
+    fn agents_are_counted_in_blocks() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        for agent in world.agents.iter_mut() {
+            agent.pos.x = 10.5;
+            agent.pos.y = 20.5;
+        }
+        world.agents[0].pos.x = 100.5;
+
+        let colors = CellColors::new(Overlay::AgentDensity, &world);
+        assert_eq!(colors.max, params.agent_count as f32 - 1.0);
+        assert!(colors.color(Vec2i::new(8, 16)) == Some(heat_color(1.0)));
+        assert!(colors.color(Vec2i::new(96, 23)) == Some(heat_color(1.0 / colors.max)));
+        assert!(colors.color(Vec2i::new(50, 50)).is_none());
+    }
+}
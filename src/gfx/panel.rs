@@ -0,0 +1,18 @@
+//! The look shared by all panels drawn over the world, like the HUD and the inspector.
+
+use crate::gfx::draw_target::DrawTarget;
+use crate::util::Rect2i;
+use sdl2::pixels::Color;
+
+pub const PANEL_COLOR: Color = Color::RGBA(20, 20, 30, 255);
+pub const BORDER_COLOR: Color = Color::RGBA(128, 128, 128, 255);
+pub const TEXT_COLOR: Color = Color::RGBA(230, 230, 230, 255);
+pub const TEXT_SCALE: u32 = 2;
+/// The space between the border of a panel and its contents, in pixels.
+pub const PADDING: u32 = 8;
+
+/// Draws the background and border of a panel.
+pub fn draw_panel(target: &mut impl DrawTarget, rect: Rect2i) {
+    target.fill_rect(rect, PANEL_COLOR);
+    target.draw_rect(rect, BORDER_COLOR);
+}
@@ -0,0 +1,248 @@
+use crate::gfx::image::{render_world, Image, Sprites};
+use crate::gfx::view::View;
+use crate::util::{parse_positive_value, parse_value, Size2i};
+use crate::world::World;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use strum_macros::{AsRefStr, EnumString};
+
+/// How the frames of a recording are saved.
+#[derive(Copy, Clone, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum RecordingFormat {
+    /// A directory of numbered PNG files, which ffmpeg can turn into a video.
+    Png,
+    /// A single animated GIF file.
+    Gif,
+}
+
+/// Settings for recordings, which are shared by the window and headless runs.
+#[derive(Clone)]
+pub struct RecordingOptions {
+    /// Simulated seconds between frames.
+    pub interval: f64,
+    pub format: RecordingFormat,
+    /// The directory or GIF file to save to, or `None` to name it after the seed and time.
+    pub path: Option<PathBuf>,
+    /// The size of the frames of headless runs. The window records at its own size.
+    pub size: Size2i,
+    /// The playback speed of GIF files, in frames per second.
+    pub fps: u32,
+}
+
+impl RecordingOptions {
+    pub fn new() -> RecordingOptions {
+        RecordingOptions {
+            interval: 1.0,
+            format: RecordingFormat::Png,
+            path: None,
+            size: Size2i::new(800, 800),
+            fps: 10,
+        }
+    }
+
+    /// Sets a single recording option by name, parsing the value from a string.
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        let key = format!("record.{}", field);
+
+        match field {
+            "interval" => self.interval = parse_positive_value(&key, value)?,
+            "format" => self.format = parse_value(&key, value)?,
+            "path" => self.path = Some(PathBuf::from(value)),
+            "width" => self.size.w = parse_positive_value(&key, value)?,
+            "height" => self.size.h = parse_positive_value(&key, value)?,
+            "fps" => self.fps = parse_positive_value(&key, value)?,
+            _ => return Err(format!("unknown recording option: {}", field)),
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for RecordingOptions {
+    fn default() -> RecordingOptions {
+        RecordingOptions::new()
+    }
+}
+
+/// A recording in progress, which captures a frame every time the simulation time passes
+/// the interval, regardless of how fast the simulation runs.
+pub struct Recording {
+    options: RecordingOptions,
+    path: PathBuf,
+    size: Size2i,
+    /// The encoder of GIF recordings.
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+    next_time: f64,
+    frames: u32,
+    sprites: Sprites,
+}
+
+impl Recording {
+    /// Starts recording frames of the given size. The first frame is captured at the next update.
+    pub fn start(
+        options: &RecordingOptions,
+        size: Size2i,
+        world: &World,
+        sprites: Sprites,
+    ) -> Result<Recording, String> {
+        let path = options.path.clone().unwrap_or_else(|| {
+            let name = format!("recording-{}-{}", world.seed, world.time.floor());
+            match options.format {
+                RecordingFormat::Png => PathBuf::from(name),
+                RecordingFormat::Gif => PathBuf::from(name + ".gif"),
+            }
+        });
+        let path_err = |err: &dyn std::fmt::Display| format!("{}: {}", path.display(), err);
+
+        let gif = match options.format {
+            RecordingFormat::Png => {
+                fs::create_dir_all(&path).map_err(|err| path_err(&err))?;
+                None
+            }
+            RecordingFormat::Gif => {
+                if size.w > u16::MAX as u32 || size.h > u16::MAX as u32 {
+                    return Err(path_err(&"the frames are too large for a GIF file"));
+                }
+                let file = File::create(&path).map_err(|err| path_err(&err))?;
+                let mut encoder =
+                    gif::Encoder::new(BufWriter::new(file), size.w as u16, size.h as u16, &[])
+                        .map_err(|err| path_err(&err))?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(|err| path_err(&err))?;
+                Some(encoder)
+            }
+        };
+
+        Ok(Recording {
+            options: options.clone(),
+            path,
+            size,
+            gif,
+            next_time: world.time,
+            frames: 0,
+            sprites,
+        })
+    }
+
+    /// The directory or GIF file the recording is saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The size of the frames.
+    pub fn size(&self) -> Size2i {
+        self.size
+    }
+
+    /// The number of frames captured so far.
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Captures a frame of the view, resized to the frame size, if it is time for one.
+    /// Frames whose time was skipped, for example by time travel, are left out.
+    pub fn update(&mut self, view: &View, world: &World) -> Result<(), String> {
+        if world.time < self.next_time {
+            return Ok(());
+        }
+        while self.next_time <= world.time {
+            self.next_time += self.options.interval;
+        }
+
+        let mut view = view.clone();
+        view.window_size = self.size;
+        let image = render_world(&view, world, &self.sprites);
+        self.add_frame(image)
+    }
+
+    fn add_frame(&mut self, mut image: Image) -> Result<(), String> {
+        let recording_path = &self.path;
+        let path_err =
+            |err: &dyn std::fmt::Display| format!("{}: {}", recording_path.display(), err);
+        match self.gif.as_mut() {
+            Some(encoder) => {
+                let mut frame = gif::Frame::from_rgba_speed(
+                    image.size.w as u16,
+                    image.size.h as u16,
+                    &mut image.pixels,
+                    10,
+                );
+                frame.delay = (100 / self.options.fps).max(1) as u16;
+                encoder.write_frame(&frame).map_err(|err| path_err(&err))?;
+            }
+            None => {
+                let path = recording_path.join(format!("frame-{:05}.png", self.frames));
+                image.save_png(&path.to_string_lossy())?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Finishes the recording. GIF files are incomplete until then.
+    pub fn finish(self) -> Result<(), String> {
+        let path = self.path;
+        let path_err = |err: &dyn std::fmt::Display| format!("{}: {}", path.display(), err);
+        if let Some(encoder) = self.gif {
+            encoder
+                .into_inner()
+                .and_then(|mut writer| writer.flush())
+                .map_err(|err| path_err(&err))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::image::fit_view;
+    use crate::world::Params;
+
+    #[test]
+    fn frames_are_captured_at_the_interval() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        let path = std::env::temp_dir().join("mini-universe-recording-test.gif");
+        let mut options = RecordingOptions::new();
+        options.set("format", "gif").unwrap();
+        options.set("interval", "0.5").unwrap();
+        options.path = Some(path.clone());
+        let size = Size2i::new(40, 30);
+        let view = fit_view(size, &world);
+
+        let mut recording = Recording::start(&options, size, &world, Sprites::plain()).unwrap();
+        while world.time < 2.0 {
+            recording.update(&view, &world).unwrap();
+            world.tick(&params, params.tick_interval);
+        }
+        recording.update(&view, &world).unwrap();
+        assert_eq!(recording.frames(), 5);
+        recording.finish().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(File::open(&path).unwrap())
+            .unwrap();
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height), (40, 30));
+            frames += 1;
+        }
+        fs::remove_file(&path).unwrap();
+        assert_eq!(frames, 5);
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let mut options = RecordingOptions::new();
+        options.set("format", "gif").unwrap();
+        assert!(options.format == RecordingFormat::Gif);
+        assert!(options.set("format", "mp4").is_err());
+        assert!(options.set("interval", "0").is_err());
+        assert!(options.set("frame_rate", "30").is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::gfx::draw_target::DrawTarget;
+use crate::gfx::font::{char_size, draw_text, text_size};
+use crate::gfx::panel::{draw_panel, BORDER_COLOR, PADDING, TEXT_COLOR, TEXT_SCALE};
+use crate::gfx::view::View;
+use crate::util::{Rect2i, Size2i};
+use crate::world::{Params, TimeController, World};
+use sdl2::pixels::Color;
+
+const ERROR_COLOR: Color = Color::RGBA(230, 80, 80, 255);
+const PROGRESS_COLOR: Color = Color::RGBA(80, 160, 230, 255);
+const PROGRESS_BAR_HEIGHT: u32 = 12;
+
+/// The time travel dialog of the window: first the target time is typed in, then the progress
+/// is shown while the time controller runs the simulation to it.
+pub struct TimeTravelDialog {
+    /// The typed target time.
+    text: String,
+    /// Why the typed time could not be used.
+    error: Option<String>,
+    /// The time being travelled to, once the typed time was submitted.
+    target_time: Option<f64>,
+}
+
+impl TimeTravelDialog {
+    pub fn new() -> TimeTravelDialog {
+        TimeTravelDialog {
+            text: String::new(),
+            error: None,
+            target_time: None,
+        }
+    }
+
+    /// Returns whether the time controller is travelling to the submitted time.
+    pub fn is_travelling(&self) -> bool {
+        self.target_time.is_some()
+    }
+
+    /// Handles typed text. Only digits and a decimal point make up a time, so other characters,
+    /// such as the T that opened the dialog, are left out.
+    pub fn text_input(&mut self, text: &str) {
+        if !self.is_travelling() {
+            self.text
+                .extend(text.chars().filter(|c| c.is_ascii_digit() || *c == '.'));
+            self.error = None;
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if !self.is_travelling() {
+            self.text.pop();
+            self.error = None;
+        }
+    }
+
+    /// Starts travelling to the typed time, or shows why it is not a valid time.
+    pub fn submit(
+        &mut self,
+        params: &Params,
+        time_controller: &mut TimeController,
+        world: &mut World,
+    ) {
+        match self.text.parse::<f64>() {
+            Ok(time) => {
+                self.target_time = Some(time);
+                time_controller.start_goto(params, world, time);
+            }
+            Err(_) if self.text.is_empty() => self.error = Some("type a time first".to_string()),
+            Err(_) => self.error = Some(format!("{} is not a valid time", self.text)),
+        }
+    }
+
+    /// Draws the dialog in the middle of the window.
+    pub fn draw(
+        &self,
+        target: &mut impl DrawTarget,
+        view: &View,
+        world: &World,
+        time_controller: &TimeController,
+    ) {
+        let (text, progress) = match self.target_time {
+            Some(time) => (
+                format!(
+                    "travelling to {} s, at {:.1} s\nEsc to cancel",
+                    time, world.time
+                ),
+                time_controller.goto_progress(world),
+            ),
+            None => (
+                format!(
+                    "travel to time: {}_\nEnter to travel, Esc to cancel",
+                    self.text
+                ),
+                None,
+            ),
+        };
+        let lines_size = text_size(&text, TEXT_SCALE);
+        let line_height = char_size(TEXT_SCALE).h;
+        let mut content_size = lines_size;
+        // The error or progress bar takes another line below the text.
+        if let Some(error) = &self.error {
+            content_size.w = content_size.w.max(text_size(error, TEXT_SCALE).w);
+            content_size.h += line_height;
+        } else if progress.is_some() {
+            content_size.h += line_height;
+        }
+
+        let panel_size = Size2i::new(content_size.w + PADDING * 2, content_size.h + PADDING * 2);
+        let panel_rect = Rect2i::new(
+            (view.window_size.w as i32 - panel_size.w as i32) / 2,
+            (view.window_size.h as i32 - panel_size.h as i32) / 2,
+            panel_size.w,
+            panel_size.h,
+        );
+        draw_panel(target, panel_rect);
+        let content_x = panel_rect.x + PADDING as i32;
+        let content_y = panel_rect.y + PADDING as i32;
+        draw_text(target, content_x, content_y, &text, TEXT_COLOR, TEXT_SCALE);
+
+        let last_line_y = content_y + lines_size.h as i32;
+        if let Some(error) = &self.error {
+            draw_text(
+                target,
+                content_x,
+                last_line_y,
+                error,
+                ERROR_COLOR,
+                TEXT_SCALE,
+            );
+        }
+        if let Some(progress) = progress {
+            let bar_y = last_line_y + (line_height - PROGRESS_BAR_HEIGHT) as i32 / 2;
+            let bar_rect = Rect2i::new(content_x, bar_y, content_size.w, PROGRESS_BAR_HEIGHT);
+            let done_width = (content_size.w as f64 * progress) as u32;
+            if done_width > 0 {
+                target.fill_rect(
+                    Rect2i::new(content_x, bar_y, done_width, PROGRESS_BAR_HEIGHT),
+                    PROGRESS_COLOR,
+                );
+            }
+            target.draw_rect(bar_rect, BORDER_COLOR);
+        }
+    }
+}
+
+impl Default for TimeTravelDialog {
+    fn default() -> TimeTravelDialog {
+        TimeTravelDialog::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_times_can_be_typed() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        let mut time_controller = TimeController::new();
+        let mut dialog = TimeTravelDialog::new();
+
+        dialog.text_input("t1");
+        dialog.text_input("2.5s");
+        dialog.text_input(".");
+        dialog.submit(&params, &mut time_controller, &mut world);
+        assert!(!dialog.is_travelling());
+        assert_eq!(dialog.error.as_deref(), Some("12.5. is not a valid time"));
+
+        dialog.backspace();
+        dialog.submit(&params, &mut time_controller, &mut world);
+        assert!(dialog.is_travelling());
+        assert_eq!(time_controller.goto_progress(&world), Some(0.0));
+    }
+}
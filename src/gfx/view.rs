@@ -6,6 +6,8 @@ const POS_MOVE_FACTOR: f32 = 500.0; // Pixels per second
 const DEFAULT_ZOOM: f32 = 20.0;
 const ZOOM_CHANGE_FACTOR: f32 = 1.2; // Exponential
 const TIME_FACTOR_CHANGE_FACTOR: f32 = 2.0; // Exponential
+/// How quickly the view catches up with a moving target. Higher is faster.
+const FOLLOW_SPEED: f32 = 5.0;
 
 #[derive(Clone)]
 /// A projection into the world.
@@ -51,11 +53,9 @@ impl View {
             }
             Scancode::Comma => {
                 self.time_factor /= TIME_FACTOR_CHANGE_FACTOR;
-                println!("time factor: {}", self.time_factor);
             }
             Scancode::Period => {
                 self.time_factor *= TIME_FACTOR_CHANGE_FACTOR;
-                println!("time factor: {}", self.time_factor);
             }
             _ => {}
         }
@@ -71,9 +71,8 @@ impl View {
         *self.keys.get(&key).unwrap_or(&false)
     }
 
-    /// Updates the view position.
-    pub fn tick(&mut self, d_time: f32) {
-        // Move view position based on keyboard input.
+    /// Returns the direction in which the movement keys move the view.
+    fn move_direction(&self) -> Vec2f {
         let mut pos_diff = Vec2f::new(0.0, 0.0);
         if self.get_key(Scancode::W) || self.get_key(Scancode::Up) {
             pos_diff.y += 1.0;
@@ -87,6 +86,22 @@ impl View {
         if self.get_key(Scancode::D) || self.get_key(Scancode::Right) {
             pos_diff.x += 1.0;
         }
-        self.pos += (pos_diff * POS_MOVE_FACTOR * d_time) / self.zoom;
+        pos_diff
+    }
+
+    /// Returns whether the user is moving the view with the keyboard.
+    pub fn is_moving(&self) -> bool {
+        self.move_direction() != Vec2f::zero()
+    }
+
+    /// Updates the view position.
+    pub fn tick(&mut self, d_time: f32) {
+        // Move view position based on keyboard input.
+        self.pos += (self.move_direction() * POS_MOVE_FACTOR * d_time) / self.zoom;
+    }
+
+    /// Moves the view smoothly towards a target position, independent of the frame rate.
+    pub fn move_towards(&mut self, target: Vec2f, d_time: f32) {
+        self.pos += (target - self.pos) * (1.0 - (-FOLLOW_SPEED * d_time).exp());
     }
 }
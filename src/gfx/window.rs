@@ -1,20 +1,124 @@
 use crate::gfx::assets::Assets;
+use crate::gfx::brain::draw_brain;
+use crate::gfx::charts::Charts;
+use crate::gfx::draw_target::CanvasTarget;
+use crate::gfx::editor::{Editor, Tool};
+use crate::gfx::follow::Follow;
+use crate::gfx::hud::{draw_hud, FpsCounter};
+use crate::gfx::image::Sprites;
+use crate::gfx::inspector::draw_inspector;
+use crate::gfx::minimap::{draw_minimap, minimap_rect, minimap_to_world_pos};
+use crate::gfx::overlay::{CellColors, Overlay};
+use crate::gfx::recording::{Recording, RecordingOptions};
+use crate::gfx::time_travel::TimeTravelDialog;
 use crate::gfx::view::View;
-use crate::gfx::world::draw_world;
+use crate::gfx::world::{agent_at_window_pos, draw_selection, draw_world, PlantGridImage};
 use crate::util::{time_ns, Size2i, Vec2f};
-use crate::world::{Params, TimeController, World};
+use crate::world::agent::genome;
+use crate::world::founders;
+use crate::world::{EndMonitor, Params, TimeController, World};
 use sdl2::event::{Event, WindowEvent};
-use sdl2::keyboard::Scancode;
+use sdl2::keyboard::{Mod, Scancode};
+use sdl2::mouse::MouseButton;
 use vek::ops::Clamp;
 
 const ENABLE_VSYNC: bool = true;
 const WINDOW_SIZE: Size2i = Size2i::new(800, 600);
+/// How long time travel may run per frame, in nanoseconds, so the window stays responsive.
+const TIME_TRAVEL_FRAME_NS: u128 = 30_000_000;
+
+/// Saves the genomes of all living agents to a file named after the seed and time.
+fn save_genomes(world: &World) {
+    let path = format!("genomes-{}-{}.txt", world.seed, world.time.floor());
+    match genome::save_genomes(&path, &world.export_genomes()) {
+        Ok(()) => println!("saved {} genomes to {}", world.agents.len(), path),
+        Err(err) => println!("could not save genomes: {}", err),
+    }
+}
+
+/// Saves all living agents as founders, including their positions, to a file named after
+/// the seed and time.
+fn save_founders(world: &World) {
+    let path = format!("founders-{}-{}.txt", world.seed, world.time.floor());
+    match founders::save_founders(&path, &world.export_founders()) {
+        Ok(()) => println!("saved {} founders to {}", world.agents.len(), path),
+        Err(err) => println!("could not save founders: {}", err),
+    }
+}
+
+/// Starts recording what the window shows, at the current size of the window.
+fn start_recording(options: &RecordingOptions, view: &View, world: &World) -> Option<Recording> {
+    let recording = Sprites::load()
+        .and_then(|sprites| Recording::start(options, view.window_size, world, sprites));
+    match recording {
+        Ok(recording) => {
+            println!("recording to {}", recording.path().display());
+            let mut recording = Some(recording);
+            record_frame(&mut recording, view, world);
+            recording
+        }
+        Err(err) => {
+            println!("could not start recording: {}", err);
+            None
+        }
+    }
+}
+
+/// Captures a frame if it is time for one. Stops recording if the frame could not be saved.
+fn record_frame(recording: &mut Option<Recording>, view: &View, world: &World) {
+    if let Some(active) = recording.as_mut() {
+        if let Err(err) = active.update(view, world) {
+            println!("could not record: {}", err);
+            stop_recording(recording);
+        }
+    }
+}
+
+fn stop_recording(recording: &mut Option<Recording>) {
+    if let Some(recording) = recording.take() {
+        let (path, frames) = (recording.path().to_path_buf(), recording.frames());
+        match recording.finish() {
+            Ok(()) => println!("recorded {} frames to {}", frames, path.display()),
+            Err(err) => println!("could not finish recording: {}", err),
+        }
+    }
+}
+
+/// Tracks the followed agent after a tick, and stops following when no agent is left.
+fn track_followed(follow: &mut Option<Follow>, world: &World) {
+    if let Some(active) = follow.as_mut() {
+        if !active.update(world) {
+            println!("[{}] no agent left to follow", world.time.floor());
+            *follow = None;
+        }
+    }
+}
 
 /// The main (GUI) loop of the program.
 /// Creates an SDL2 window and runs an event loop.
-pub fn main_loop(params: &Params) {
+pub fn main_loop(params: &Params, recording_options: &RecordingOptions) {
     let mut world = World::new(params);
     let mut time_controller = TimeController::new();
+    let mut end_monitor = EndMonitor::new(&world);
+    // After the run ended, the user may resume it without it ending again.
+    let mut ended = false;
+    let mut recording: Option<Recording> = None;
+    // The id of the agent shown in the inspector.
+    let mut selected: Option<u64> = None;
+    let mut follow: Option<Follow> = None;
+    let mut show_brain = false;
+    let mut show_hud = true;
+    let mut show_minimap = true;
+    // Whether the view is being dragged around the minimap.
+    let mut dragging_minimap = false;
+    let mut charts = Charts::new();
+    let mut show_charts = false;
+    let mut overlay = Overlay::Plants;
+    let mut plant_grid_image = PlantGridImage::new();
+    let mut editor = Editor::new();
+    let mut fps_counter = FpsCounter::new();
+    // The simulation waits while the time travel dialog is open.
+    let mut time_travel: Option<TimeTravelDialog> = None;
     let mut view = View::new(
         WINDOW_SIZE,
         Vec2f::new(
@@ -40,8 +144,8 @@ pub fn main_loop(params: &Params) {
         }
         canvas = canvasbuilder.build().unwrap();
     }
-    let mut texture_creator = canvas.texture_creator();
-    let mut assets = Assets::load(&mut texture_creator);
+    let texture_creator = canvas.texture_creator();
+    let mut assets = Assets::load(&texture_creator);
 
     let mut event_pump = sdl_context.event_pump().unwrap();
     let mut prev_nano_time = time_ns();
@@ -50,6 +154,7 @@ pub fn main_loop(params: &Params) {
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => {
+                    stop_recording(&mut recording);
                     break 'event_loop;
                 }
 
@@ -60,14 +165,102 @@ pub fn main_loop(params: &Params) {
                     _ => {}
                 },
 
+                // While the time travel dialog is open, the keys are used to type in it.
                 Event::KeyDown {
                     scancode: Some(scancode),
                     ..
+                } if time_travel.is_some() => {
+                    let dialog = time_travel.as_mut().unwrap();
+                    match scancode {
+                        Scancode::Escape => {
+                            if dialog.is_travelling() {
+                                time_controller.cancel_goto();
+                                end_monitor = EndMonitor::new(&world);
+                                ended = false;
+                            }
+                            time_travel = None;
+                        }
+                        Scancode::Return | Scancode::KpEnter => {
+                            dialog.submit(params, &mut time_controller, &mut world);
+                        }
+                        Scancode::Backspace => dialog.backspace(),
+                        _ => {}
+                    }
+                }
+
+                Event::TextInput { text, .. } => {
+                    if let Some(dialog) = time_travel.as_mut() {
+                        dialog.text_input(&text);
+                    }
+                }
+
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    keymod,
+                    ..
                 } => {
                     if scancode == Scancode::R {
+                        // The recording can't go back in time.
+                        stop_recording(&mut recording);
+                        follow = None;
                         world = World::new(&params);
+                        end_monitor = EndMonitor::new(&world);
+                        ended = false;
                     } else if scancode == Scancode::T {
-                        time_controller.goto_prompt(params, &mut world);
+                        stop_recording(&mut recording);
+                        follow = None;
+                        editor.mouse_up();
+                        time_travel = Some(TimeTravelDialog::new());
+                    } else if scancode == Scancode::G {
+                        save_genomes(&world);
+                    } else if scancode == Scancode::F {
+                        save_founders(&world);
+                    } else if scancode == Scancode::V {
+                        if recording.is_some() {
+                            stop_recording(&mut recording);
+                        } else {
+                            recording = start_recording(recording_options, &view, &world);
+                        }
+                    } else if scancode == Scancode::B {
+                        show_brain = !show_brain;
+                    } else if scancode == Scancode::M {
+                        show_minimap = !show_minimap;
+                    } else if scancode == Scancode::O {
+                        overlay = overlay.next();
+                    } else if scancode == Scancode::E {
+                        editor.next_tool();
+                    } else if scancode == Scancode::N {
+                        editor.next_species();
+                    } else if scancode == Scancode::LeftBracket {
+                        editor.resize_brush(false);
+                    } else if scancode == Scancode::RightBracket {
+                        editor.resize_brush(true);
+                    } else if scancode == Scancode::H {
+                        show_hud = !show_hud;
+                    } else if scancode == Scancode::P {
+                        // With shift, the charts show the next gene.
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            charts.next_gene();
+                            show_charts = true;
+                        } else {
+                            show_charts = !show_charts;
+                        }
+                    } else if scancode == Scancode::C {
+                        if follow.is_some() {
+                            follow = None;
+                            println!("stopped following");
+                        } else if let Some(id) = selected {
+                            // With shift, the descendants are followed after the agent dies.
+                            let descendants = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                            follow = Some(Follow::new(&world, id, descendants));
+                            if descendants {
+                                println!("following agent {} and its descendants", id);
+                            } else {
+                                println!("following agent {}", id);
+                            }
+                        } else {
+                            println!("select an agent to follow first");
+                        }
                     } else {
                         view.key_down(scancode);
                     }
@@ -80,6 +273,48 @@ pub fn main_loop(params: &Params) {
                     view.key_up(scancode);
                 }
 
+                Event::MouseButtonDown {
+                    mouse_btn, x, y, ..
+                } => {
+                    let pos = Vec2f::new(x as f32, y as f32);
+                    let minimap_pos = minimap_to_world_pos(&view, &world, pos)
+                        .filter(|_| show_minimap && mouse_btn == MouseButton::Left);
+                    if let Some(world_pos) = minimap_pos {
+                        view.pos = world_pos;
+                        follow = None;
+                        dragging_minimap = true;
+                    } else if time_travel.is_some() {
+                        // The world can't be edited while it travels through time.
+                    } else if editor.tool != Tool::Select {
+                        let shift = sdl_context
+                            .keyboard()
+                            .mod_state()
+                            .intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                        let agent =
+                            editor.mouse_down(mouse_btn, shift, pos, params, &view, &mut world);
+                        if agent.is_some() {
+                            selected = agent;
+                        }
+                    } else if mouse_btn == MouseButton::Left {
+                        selected = agent_at_window_pos(&view, &world, pos);
+                    }
+                }
+
+                Event::MouseMotion { x, y, .. } => {
+                    let pos = Vec2f::new(x as f32, y as f32);
+                    editor.mouse_motion(pos);
+                    if dragging_minimap {
+                        if let Some(world_pos) = minimap_to_world_pos(&view, &world, pos) {
+                            view.pos = world_pos;
+                        }
+                    }
+                }
+
+                Event::MouseButtonUp { .. } => {
+                    dragging_minimap = false;
+                    editor.mouse_up();
+                }
+
                 Event::MouseWheel { y, .. } => {
                     view.change_zoom(y as f32);
                 }
@@ -92,14 +327,89 @@ pub fn main_loop(params: &Params) {
         let raw_d_time: f32 = (cur_nano_time - prev_nano_time) as f32 / 1e9f32;
         // Clamp d_time between 1 nanosecond and 1 second to prevent divide by zero and runaway.
         let d_time: f32 = raw_d_time.clamped(1e-9f32, 1.0);
+        fps_counter.update(raw_d_time);
 
         view.tick(d_time);
 
-        if !view.paused {
-            time_controller.tick(params, &mut world, d_time * view.time_factor);
+        if time_travel.is_some() {
+            let deadline = time_ns() + TIME_TRAVEL_FRAME_NS;
+            if time_controller.continue_goto(params, &mut world, deadline)
+                && time_travel.as_ref().unwrap().is_travelling()
+            {
+                end_monitor = EndMonitor::new(&world);
+                ended = false;
+                time_travel = None;
+            }
+        } else if !view.paused && ended {
+            time_controller.tick(params, &mut world, d_time * view.time_factor, |world| {
+                track_followed(&mut follow, world);
+                record_frame(&mut recording, &view, world);
+            });
+        } else if !view.paused {
+            if let Some(reason) = time_controller.tick_until_end(
+                params,
+                &mut world,
+                d_time * view.time_factor,
+                &mut end_monitor,
+                |world| {
+                    track_followed(&mut follow, world);
+                    record_frame(&mut recording, &view, world);
+                },
+            ) {
+                println!("[{}] run ended: {}", world.time.floor(), reason);
+                view.paused = true;
+                ended = true;
+            }
+        }
+
+        if time_travel.is_none() {
+            editor.update(params, &view, &mut world);
         }
 
-        draw_world(&mut canvas, &mut assets, &view, &world);
+        // Moving the camera by hand stops following.
+        if view.is_moving() {
+            follow = None;
+        }
+        if let Some(follow) = follow.as_ref() {
+            follow.move_camera(&mut view, &world, d_time);
+            selected = Some(follow.agent);
+        }
+
+        {
+            let mut target = CanvasTarget::new(&mut canvas, &mut assets);
+            let cell_colors = CellColors::new(overlay, &world);
+            plant_grid_image.update(&world, &cell_colors);
+            draw_world(&mut target, &view, &world, &plant_grid_image);
+            editor.draw(&mut target, &view);
+            cell_colors.draw_legend(&mut target, &view);
+            if show_charts {
+                charts.draw(&mut target, &view, &world.history);
+            }
+            // Deselect the agent once it has died.
+            selected = selected.filter(|id| world.agent_by_id(*id).is_some());
+            if let Some(agent) = selected.and_then(|id| world.agent_by_id(id)) {
+                draw_selection(&mut target, &view, &world, agent);
+                draw_inspector(&mut target, agent, &world);
+                if show_brain {
+                    // Both sit in the top right corner, so the brain moves below the minimap.
+                    let top = if show_minimap {
+                        minimap_rect(&view, &world).h as i32
+                    } else {
+                        0
+                    };
+                    draw_brain(&mut target, &view, agent, &world, top);
+                }
+            }
+            if show_minimap {
+                draw_minimap(&mut target, &view, &world, &cell_colors);
+            }
+            if show_hud {
+                draw_hud(&mut target, &view, &world, fps_counter.fps(), &editor);
+            }
+            if let Some(dialog) = time_travel.as_ref() {
+                dialog.draw(&mut target, &view, &world, &time_controller);
+            }
+        }
         canvas.present();
 
         prev_nano_time = cur_nano_time;
@@ -1,18 +1,21 @@
-use crate::gfx::assets::Assets;
+use crate::gfx::draw_target::{DrawTarget, Sprite};
+use crate::gfx::image::Image;
+use crate::gfx::overlay::{CellColors, Overlay, WALL_COLOR};
 use crate::gfx::view::View;
-use crate::util::{rect2i_collides, vec2f_to_vec2i, Rect2f, Rect2i, Vec2f, Vec2i};
-use crate::world::World;
-use sdl2::{
-    pixels::Color,
-    rect::{Point, Rect},
-    render::Canvas,
-    video::Window,
-};
+use crate::util::{rect2i_collides, vec2f_to_vec2i, Rect2f, Rect2i, Size2i, Vec2f, Vec2i};
+use crate::world::{Agent, Edit, PlantSpecies, Topology, World};
+use sdl2::pixels::Color;
+use std::ops::Range;
+use strum::IntoEnumIterator;
 
 const BACKGROUND_COLOR: Color = Color::RGBA(0, 0, 0, 255);
-const PLANT_COLOR: Color = Color::RGBA(96, 255, 32, 255);
 const AGENT_MEASURE_COLOR: Color = Color::RGBA(100, 100, 255, 200);
 const AGENT_MEASURE_SIZE: f32 = 0.25;
+const SELECTION_COLOR: Color = Color::RGBA(255, 255, 255, 255);
+/// How far the selection rectangle is drawn outside an agent, in pixels.
+const SELECTION_MARGIN: i32 = 3;
+/// How far agents and their measurement points can reach beyond their cell, in world units.
+const WRAP_MARGIN: f32 = 12.0;
 
 /// Translate a position in the world to a position on the window.
 fn world_to_window_pos(view: &View, pos: Vec2f) -> Vec2f {
@@ -41,127 +44,362 @@ fn world_to_window_rect(view: &View, rect: Rect2f) -> Rect2i {
     )
 }
 
-/// Draw the world.
-pub fn draw_world(canvas: &mut Canvas<Window>, assets: &mut Assets, view: &View, world: &World) {
-    canvas.set_draw_color(BACKGROUND_COLOR);
-    canvas.clear();
+/// Translate a position on the window to a position in the world.
+pub fn window_to_world_pos(view: &View, pos: Vec2f) -> Vec2f {
+    // The window is Y-down but the world is Y-up, so flip it.
+    let centered_pos = Vec2f::new(
+        pos.x - view.window_size.w as f32 / 2.0,
+        view.window_size.h as f32 / 2.0 - pos.y,
+    );
 
-    let mut prev_color = Color::RGBA(0, 0, 0, 0);
-    let window_rect = Rect2i::new(0, 0, view.window_size.w, view.window_size.h);
+    view.pos + centered_pos / view.zoom
+}
+
+/// Returns the part of the world that is visible on the window.
+pub fn visible_world_rect(view: &View) -> Rect2f {
+    let bottom_left = window_to_world_pos(view, Vec2f::new(0.0, view.window_size.h as f32));
+    let top_right = window_to_world_pos(view, Vec2f::new(view.window_size.w as f32, 0.0));
 
-    // Draw plant grid.
-    for row in 0..world.plant_grid.size.h {
-        for col in 0..world.plant_grid.size.w {
-            let draw_rect =
-                world_to_window_rect(view, Rect2f::new(col as f32, row as f32, 1.0, 1.0));
-            if !rect2i_collides(draw_rect, window_rect) {
-                // Plant cell is not on screen.
-                continue;
+    Rect2f::new(
+        bottom_left.x,
+        bottom_left.y,
+        top_right.x - bottom_left.x,
+        top_right.y - bottom_left.y,
+    )
+}
+
+/// Returns the offsets of all visible copies of the world.
+/// On a torus the world repeats in every direction, otherwise there is only one copy.
+fn visible_world_offsets(view: &View, world: &World) -> Vec<Vec2f> {
+    let size = Vec2f::new(
+        world.plant_grid.size.w as f32,
+        world.plant_grid.size.h as f32,
+    );
+    match world.plant_grid.topology {
+        Topology::Walled => vec![Vec2f::zero()],
+        Topology::Torus => {
+            let visible_rect = visible_world_rect(view);
+            let first_tile = ((Vec2f::new(visible_rect.x, visible_rect.y) - WRAP_MARGIN) / size)
+                .map(|tile| tile.floor() as i32);
+            let last_tile = ((Vec2f::new(
+                visible_rect.x + visible_rect.w,
+                visible_rect.y + visible_rect.h,
+            ) + WRAP_MARGIN)
+                / size)
+                .map(|tile| tile.floor() as i32);
+
+            let mut offsets = Vec::new();
+            for tile_y in first_tile.y..=last_tile.y {
+                for tile_x in first_tile.x..=last_tile.x {
+                    offsets.push(Vec2f::new(tile_x as f32, tile_y as f32) * size);
+                }
             }
+            offsets
+        }
+    }
+}
+
+/// Draw the world, with a picture of the plant grid.
+pub fn draw_world(
+    target: &mut impl DrawTarget,
+    view: &View,
+    world: &World,
+    plant_grid_image: &PlantGridImage,
+) {
+    target.clear(BACKGROUND_COLOR);
+
+    let window_rect = Rect2i::new(0, 0, view.window_size.w, view.window_size.h);
+    let offsets = visible_world_offsets(view, world);
+
+    let grid_rects: Vec<Rect2i> = offsets
+        .iter()
+        .map(|offset| {
+            world_to_window_rect(
+                view,
+                Rect2f::new(
+                    offset.x,
+                    offset.y,
+                    world.plant_grid.size.w as f32,
+                    world.plant_grid.size.h as f32,
+                ),
+            )
+        })
+        .filter(|grid_rect| rect2i_collides(*grid_rect, window_rect))
+        .collect();
+    target.draw_plant_grid(
+        &plant_grid_image.image,
+        plant_grid_image.changed_rows.clone(),
+        &grid_rects,
+    );
+
+    if world.plant_grid.topology == Topology::Walled {
+        let walls_rect = world_to_window_rect(
+            view,
+            Rect2f::new(
+                0.0,
+                0.0,
+                world.plant_grid.size.w as f32,
+                world.plant_grid.size.h as f32,
+            ),
+        );
+        target.draw_rect(walls_rect, WALL_COLOR);
+    }
+
+    for offset in offsets.iter() {
+        for agent in world.agents.iter() {
+            draw_agent(target, view, agent, *offset, window_rect);
+        }
+    }
+}
+
+/// Returns the id of the agent at a position on the window.
+/// If agents overlap, the one drawn on top is returned.
+pub fn agent_at_window_pos(view: &View, world: &World, pos: Vec2f) -> Option<u64> {
+    let world_pos = window_to_world_pos(view, pos);
+    let offsets = visible_world_offsets(view, world);
+    world
+        .agents
+        .iter()
+        .rev()
+        .find(|agent| {
+            let bounding_rect = agent.get_bounding_rect();
+            offsets
+                .iter()
+                .any(|offset| bounding_rect.contains_point(world_pos - *offset))
+        })
+        .map(|agent| agent.id)
+}
+
+/// Draw a rectangle around every visible copy of the selected agent.
+pub fn draw_selection(target: &mut impl DrawTarget, view: &View, world: &World, agent: &Agent) {
+    for offset in visible_world_offsets(view, world) {
+        let mut bounding_rect = agent.get_bounding_rect();
+        bounding_rect.x += offset.x;
+        bounding_rect.y += offset.y;
+        let draw_rect = world_to_window_rect(view, bounding_rect);
+        target.draw_rect(
+            Rect2i::new(
+                draw_rect.x - SELECTION_MARGIN,
+                draw_rect.y - SELECTION_MARGIN,
+                draw_rect.w + SELECTION_MARGIN as u32 * 2,
+                draw_rect.h + SELECTION_MARGIN as u32 * 2,
+            ),
+            SELECTION_COLOR,
+        );
+    }
+}
+
+/// The plant grid in the colors of an overlay, as an image with one pixel per cell.
+/// The world is Y-up but images are Y-down, so the first row of the image is the top row.
+pub struct PlantGridImage {
+    pub image: Image,
+    /// The rows of the image from the first to the last that the last update redrew.
+    pub changed_rows: Range<u32>,
+    /// The overlay the image shows, or `None` before it is first drawn.
+    overlay: Option<Overlay>,
+    /// The density of every species in every row when the image was last updated.
+    /// Rows whose densities differ are redrawn.
+    row_densities: Vec<Vec<u32>>,
+    /// The number of edits made by hand when the image was last updated.
+    edit_count: usize,
+    /// The time of the world when the image was last updated.
+    time: f64,
+}
 
-            let density = world
-                .plant_grid
-                .get_density_unchecked(Vec2i::new(col as i32, row as i32));
-            if density == 0 {
-                continue;
+impl PlantGridImage {
+    pub fn new() -> PlantGridImage {
+        PlantGridImage {
+            image: Image::new(Size2i::new(0, 0)),
+            changed_rows: 0..0,
+            overlay: None,
+            row_densities: Vec::new(),
+            edit_count: 0,
+            time: 0.0,
+        }
+    }
+
+    /// Brings the image up to date with the plant grid. Only the rows whose plant densities
+    /// changed or that were edited by hand are redrawn, unless the overlay changed, its colors
+    /// change even where the plants don't, or the world went back in time.
+    pub fn update(&mut self, world: &World, cell_colors: &CellColors) {
+        let plant_grid = &world.plant_grid;
+        let size = plant_grid.size;
+        let overlay = cell_colors.overlay();
+        let edits = world.edits();
+        let redraw_all = self.image.size != size
+            || self.overlay != Some(overlay)
+            || !overlay.shows_only_plants()
+            || edits.len() < self.edit_count
+            || world.time < self.time;
+        if self.image.size != size {
+            self.image = Image::new(size);
+        }
+
+        let mut redraw = vec![redraw_all; size.h as usize];
+        if !redraw_all {
+            for (species, row_densities) in PlantSpecies::iter().zip(self.row_densities.iter()) {
+                for (row, density) in plant_grid.row_densities(species).iter().enumerate() {
+                    redraw[row] |= *density != row_densities[row];
+                }
             }
-            let color = Color::RGBA(
-                ((PLANT_COLOR.r as i32 * density as i32) / 255) as u8,
-                ((PLANT_COLOR.g as i32 * density as i32) / 255) as u8,
-                ((PLANT_COLOR.b as i32 * density as i32) / 255) as u8,
-                ((PLANT_COLOR.a as i32 * density as i32) / 255) as u8,
-            );
-            if color != prev_color {
-                canvas.set_draw_color(color);
-                prev_color = color;
+            // Walls don't change the densities, so the rows of edits are redrawn as well.
+            for (_, edit) in edits[self.edit_count..].iter() {
+                if let Edit::Paint { center, radius, .. }
+                | Edit::Erase { center, radius }
+                | Edit::SetWalls { center, radius, .. } = edit
+                {
+                    for cell in plant_grid.brush_cells(*center, *radius) {
+                        redraw[cell.y as usize] = true;
+                    }
+                }
             }
+        }
+        self.overlay = Some(overlay);
+        self.row_densities = PlantSpecies::iter()
+            .map(|species| plant_grid.row_densities(species).to_vec())
+            .collect();
+        self.edit_count = edits.len();
+        self.time = world.time;
 
-            canvas
-                .fill_rect(Rect::new(
-                    draw_rect.x,
-                    draw_rect.y,
-                    draw_rect.w,
-                    draw_rect.h,
-                ))
-                .unwrap();
+        for row in (0..size.h).filter(|row| redraw[*row as usize]) {
+            for col in 0..size.w {
+                let color = cell_colors
+                    .color(Vec2i::new(col as i32, row as i32))
+                    .unwrap_or(BACKGROUND_COLOR);
+                self.image
+                    .set_pixel(col, size.h - 1 - row, [color.r, color.g, color.b, 255]);
+            }
         }
+        let first = redraw.iter().position(|redrawn| *redrawn);
+        let last = redraw.iter().rposition(|redrawn| *redrawn);
+        self.changed_rows = match (first, last) {
+            (Some(first), Some(last)) => size.h - 1 - last as u32..size.h - first as u32,
+            _ => 0..0,
+        };
     }
+}
 
-    // Draw agents.
-    canvas.set_draw_color(AGENT_MEASURE_COLOR);
-    for agent in world.agents.iter() {
-        let agent_color = agent.genes.get_color();
-        assets.agent_sprite.set_color_mod(
+impl Default for PlantGridImage {
+    fn default() -> PlantGridImage {
+        PlantGridImage::new()
+    }
+}
+
+/// Draw an agent and its measurement points, translated by the given offset.
+fn draw_agent(
+    target: &mut impl DrawTarget,
+    view: &View,
+    agent: &Agent,
+    offset: Vec2f,
+    window_rect: Rect2i,
+) {
+    let agent_color = agent.genes.get_color();
+    let mut bounding_rect = agent.get_bounding_rect();
+    bounding_rect.x += offset.x;
+    bounding_rect.y += offset.y;
+    let draw_rect = world_to_window_rect(view, bounding_rect);
+    if !rect2i_collides(draw_rect, window_rect) {
+        // Agent is not on screen.
+        return;
+    }
+
+    // Draw agent
+    let angle = -agent.angle.to_degrees() as f64;
+    target.draw_sprite(
+        Sprite::Agent,
+        draw_rect,
+        angle,
+        [
             (agent_color.x * 255.0) as u8,
             (agent_color.y * 255.0) as u8,
             (agent_color.z * 255.0) as u8,
-        );
-        let draw_rect = world_to_window_rect(view, agent.get_bounding_rect());
-        if !rect2i_collides(draw_rect, window_rect) {
-            // Agent is not on screen.
-            continue;
-        }
+        ],
+    );
+    target.draw_sprite(Sprite::AgentEyes, draw_rect, angle, [255, 255, 255]);
 
-        // Draw agent
-        canvas
-            .copy_ex(
-                &assets.agent_sprite,
-                None,
-                Rect::new(draw_rect.x, draw_rect.y, draw_rect.w, draw_rect.h),
-                -agent.angle.to_degrees() as f64,
-                Point::new(draw_rect.w as i32 / 2, draw_rect.h as i32 / 2),
-                false,
-                false,
-            )
-            .unwrap();
-        canvas
-            .copy_ex(
-                &assets.agent_eyes_sprite,
-                None,
-                Rect::new(draw_rect.x, draw_rect.y, draw_rect.w, draw_rect.h),
-                -agent.angle.to_degrees() as f64,
-                Point::new(draw_rect.w as i32 / 2, draw_rect.h as i32 / 2),
-                false,
-                false,
-            )
-            .unwrap();
+    // Draw measurement points.
+    let left_measure_pos = agent.get_left_measure_pos() + offset;
+    let right_measure_pos = agent.get_right_measure_pos() + offset;
+    let left_measure_rect = world_to_window_rect(
+        view,
+        Rect2f::new(
+            left_measure_pos.x - AGENT_MEASURE_SIZE / 2.0,
+            left_measure_pos.y - AGENT_MEASURE_SIZE / 2.0,
+            AGENT_MEASURE_SIZE,
+            AGENT_MEASURE_SIZE,
+        ),
+    );
+    let right_measure_rect = world_to_window_rect(
+        view,
+        Rect2f::new(
+            right_measure_pos.x - AGENT_MEASURE_SIZE / 2.0,
+            right_measure_pos.y - AGENT_MEASURE_SIZE / 2.0,
+            AGENT_MEASURE_SIZE,
+            AGENT_MEASURE_SIZE,
+        ),
+    );
+    target.fill_rect(left_measure_rect, AGENT_MEASURE_COLOR);
+    target.fill_rect(right_measure_rect, AGENT_MEASURE_COLOR);
+}
 
-        // Draw measurement points.
-        let left_measure_pos = agent.get_left_measure_pos();
-        let right_measure_pos = agent.get_right_measure_pos();
-        let left_measure_rect = world_to_window_rect(
-            view,
-            Rect2f::new(
-                left_measure_pos.x - AGENT_MEASURE_SIZE / 2.0,
-                left_measure_pos.y - AGENT_MEASURE_SIZE / 2.0,
-                AGENT_MEASURE_SIZE,
-                AGENT_MEASURE_SIZE,
-            ),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Size2i;
+    use crate::world::Params;
+
+    #[test]
+    fn agents_are_found_at_window_positions() {
+        let mut world = World::new_seeded(&Params::default(), 1);
+        world.agents[2].pos = Vec2f::new(0.5, 100.0);
+        let view = View::new(Size2i::new(400, 400), Vec2f::new(0.0, 100.0));
+
+        assert_eq!(
+            agent_at_window_pos(&view, &world, Vec2f::new(210.0, 200.0)),
+            Some(world.agents[2].id)
         );
-        let right_measure_rect = world_to_window_rect(
-            view,
-            Rect2f::new(
-                right_measure_pos.x - AGENT_MEASURE_SIZE / 2.0,
-                right_measure_pos.y - AGENT_MEASURE_SIZE / 2.0,
-                AGENT_MEASURE_SIZE,
-                AGENT_MEASURE_SIZE,
-            ),
+        assert_eq!(
+            agent_at_window_pos(&view, &world, Vec2f::new(300.0, 300.0)),
+            None
         );
-        canvas
-            .fill_rect(Rect::new(
-                left_measure_rect.x,
-                left_measure_rect.y,
-                left_measure_rect.w,
-                left_measure_rect.h,
-            ))
-            .unwrap();
-        canvas
-            .fill_rect(Rect::new(
-                right_measure_rect.x,
-                right_measure_rect.y,
-                right_measure_rect.w,
-                right_measure_rect.h,
-            ))
-            .unwrap();
+        // On a torus the agent is also found on the other side of the border.
+        world.agents[2].pos = Vec2f::new(199.5, 100.0);
+        assert_eq!(
+            agent_at_window_pos(&view, &world, Vec2f::new(190.0, 200.0)),
+            Some(world.agents[2].id)
+        );
+    }
+
+    #[test]
+    fn plant_grid_images_redraw_changed_rows() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        let mut plant_grid_image = PlantGridImage::new();
+        plant_grid_image.update(&world, &CellColors::new(Overlay::Plants, &world));
+        assert_eq!(plant_grid_image.changed_rows, 0..200);
+        plant_grid_image.update(&world, &CellColors::new(Overlay::Plants, &world));
+        assert_eq!(plant_grid_image.changed_rows, 0..0);
+
+        // Walls on empty cells don't change the densities, but their rows are redrawn.
+        world.plant_grid.erase(Vec2f::new(50.5, 50.5), 5.0);
+        plant_grid_image.update(&world, &CellColors::new(Overlay::Plants, &world));
+        let wall = Edit::SetWalls {
+            center: Vec2f::new(50.5, 50.5),
+            radius: 2.0,
+            wall: true,
+        };
+        world.edit(&params, wall);
+        plant_grid_image.update(&world, &CellColors::new(Overlay::Plants, &world));
+        assert_eq!(plant_grid_image.changed_rows, 147..152);
+
+        // Long enough for the plants to regenerate.
+        for _ in 0..300 {
+            world.tick(&params, params.tick_interval);
+        }
+        let cell_colors = CellColors::new(Overlay::Plants, &world);
+        plant_grid_image.update(&world, &cell_colors);
+        let mut redrawn = PlantGridImage::new();
+        redrawn.update(&world, &cell_colors);
+        assert!(plant_grid_image.image == redrawn.image);
     }
 }
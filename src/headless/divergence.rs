@@ -0,0 +1,286 @@
+use crate::util::Vec2i;
+use crate::world::{Params, PlantSpecies, World};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use strum::IntoEnumIterator;
+
+/// The state hashes of a world after a single tick.
+#[derive(PartialEq)]
+pub struct TickHashes {
+    pub world: u64,
+    pub plant_grid: u64,
+    pub agents: Vec<u64>,
+}
+
+impl TickHashes {
+    pub fn new(world: &World) -> TickHashes {
+        TickHashes {
+            world: world.state_hash(),
+            plant_grid: world.plant_grid.state_hash(),
+            agents: world
+                .agents
+                .iter()
+                .map(|agent| agent.state_hash())
+                .collect(),
+        }
+    }
+
+    /// Formats the hashes as a trace line: the world hash, the plant grid hash
+    /// and the hash of every agent, in hexadecimal.
+    fn to_line(&self, tick: u32) -> String {
+        let mut line = format!("{} {:016x} {:016x}", tick, self.world, self.plant_grid);
+        for agent in self.agents.iter() {
+            line.push_str(&format!(" {:016x}", agent));
+        }
+        line
+    }
+
+    fn from_line(line: &str) -> Result<(u32, TickHashes), String> {
+        let mut fields = line.split_whitespace();
+        let tick = fields
+            .next()
+            .and_then(|field| field.parse::<u32>().ok())
+            .ok_or_else(|| format!("invalid trace line: {}", line))?;
+        let hashes = fields
+            .map(|field| u64::from_str_radix(field, 16))
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|_| format!("invalid trace line: {}", line))?;
+        if hashes.len() < 2 {
+            return Err(format!("invalid trace line: {}", line));
+        }
+
+        Ok((
+            tick,
+            TickHashes {
+                world: hashes[0],
+                plant_grid: hashes[1],
+                agents: hashes[2..].to_vec(),
+            },
+        ))
+    }
+}
+
+/// The first point where two runs differ.
+pub struct Divergence {
+    pub tick: u32,
+    /// The agent counts of both runs, if they differ.
+    pub agent_counts: Option<(usize, usize)>,
+    /// The index of the first agent whose state differs.
+    pub agent: Option<usize>,
+    pub plant_grid_differs: bool,
+    /// The first plant cell that differs. Only known when comparing live runs.
+    pub cell: Option<(PlantSpecies, Vec2i)>,
+}
+
+impl Divergence {
+    /// Compares the hashes of two runs at the same tick.
+    fn from_hashes(tick: u32, hashes_a: &TickHashes, hashes_b: &TickHashes) -> Divergence {
+        let agent_counts = if hashes_a.agents.len() != hashes_b.agents.len() {
+            Some((hashes_a.agents.len(), hashes_b.agents.len()))
+        } else {
+            None
+        };
+        let agent = hashes_a
+            .agents
+            .iter()
+            .zip(hashes_b.agents.iter())
+            .position(|(agent_a, agent_b)| agent_a != agent_b);
+
+        Divergence {
+            tick,
+            agent_counts,
+            agent,
+            plant_grid_differs: hashes_a.plant_grid != hashes_b.plant_grid,
+            cell: None,
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "runs diverge at tick {}", self.tick)?;
+        if let Some((count_a, count_b)) = self.agent_counts {
+            writeln!(f, "  agent count: {} vs {}", count_a, count_b)?;
+        }
+        if let Some(agent) = self.agent {
+            writeln!(f, "  first differing agent: #{}", agent)?;
+        }
+        if let Some((species, pos)) = self.cell {
+            writeln!(
+                f,
+                "  first differing plant cell: {} at ({}, {})",
+                species.as_ref(),
+                pos.x,
+                pos.y
+            )?;
+        } else if self.plant_grid_differs {
+            writeln!(f, "  plant grid differs")?;
+        }
+        if self.agent_counts.is_none() && self.agent.is_none() && !self.plant_grid_differs {
+            writeln!(
+                f,
+                "  agents and plants are equal, the random number generator or records differ"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds the first plant cell that differs between two worlds.
+fn first_differing_cell(world_a: &World, world_b: &World) -> Option<(PlantSpecies, Vec2i)> {
+    let width = world_a.plant_grid.size.w as usize;
+    for species in PlantSpecies::iter() {
+        let densities_a = world_a.plant_grid.densities(species);
+        let densities_b = world_b.plant_grid.densities(species);
+        if let Some(idx) = densities_a
+            .iter()
+            .zip(densities_b.iter())
+            .position(|(density_a, density_b)| density_a != density_b)
+        {
+            return Some((
+                species,
+                Vec2i::new((idx % width) as i32, (idx / width) as i32),
+            ));
+        }
+    }
+
+    None
+}
+
+/// Runs two configurations side by side for the given number of ticks,
+/// and returns where they diverge first, if they do.
+pub fn compare_runs(params_a: &Params, params_b: &Params, ticks: u32) -> Option<Divergence> {
+    let mut world_a = World::new(params_a);
+    let mut world_b = World::new(params_b);
+    for tick in 0..=ticks {
+        if tick > 0 {
+            world_a.tick(params_a, params_a.tick_interval);
+            world_b.tick(params_b, params_b.tick_interval);
+        }
+
+        let hashes_a = TickHashes::new(&world_a);
+        let hashes_b = TickHashes::new(&world_b);
+        if hashes_a != hashes_b {
+            let mut divergence = Divergence::from_hashes(tick, &hashes_a, &hashes_b);
+            if world_a.plant_grid.size == world_b.plant_grid.size {
+                divergence.cell = first_differing_cell(&world_a, &world_b);
+            }
+            return Some(divergence);
+        }
+    }
+
+    None
+}
+
+/// Runs a configuration for the given number of ticks and writes the state hashes
+/// of every tick to a trace file, so runs of different builds can be compared.
+pub fn record_trace(params: &Params, ticks: u32, path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut writer = BufWriter::new(file);
+    let mut world = World::new(params);
+    let write_err = |err: std::io::Error| format!("{}: {}", path, err);
+
+    writeln!(writer, "# mini-universe hash trace, seed {}", world.seed).map_err(write_err)?;
+    for tick in 0..=ticks {
+        if tick > 0 {
+            world.tick(params, params.tick_interval);
+        }
+        writeln!(writer, "{}", TickHashes::new(&world).to_line(tick)).map_err(write_err)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a trace file written by `record_trace`.
+pub fn read_trace(path: &str) -> Result<Vec<(u32, TickHashes)>, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut trace = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|err| format!("{}: {}", path, err))?;
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        trace.push(TickHashes::from_line(&line)?);
+    }
+
+    Ok(trace)
+}
+
+/// Compares two recorded traces and returns where they diverge first, if they do.
+/// Entries are compared by tick, so both traces must record the same ticks. Only the ticks
+/// that both traces contain are compared, so one trace may be longer than the other.
+pub fn compare_traces(
+    trace_a: &[(u32, TickHashes)],
+    trace_b: &[(u32, TickHashes)],
+) -> Result<Option<Divergence>, String> {
+    for ((tick_a, hashes_a), (tick_b, hashes_b)) in trace_a.iter().zip(trace_b.iter()) {
+        if tick_a != tick_b {
+            return Err(format!(
+                "traces record different ticks: {} in a, {} in b",
+                tick_a, tick_b
+            ));
+        }
+        if hashes_a != hashes_b {
+            return Ok(Some(Divergence::from_hashes(*tick_a, hashes_a, hashes_b)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_runs_do_not_diverge() {
+        let mut params = Params::default();
+        params.seed = Some(3);
+        assert!(compare_runs(&params, &params, 200).is_none());
+    }
+
+    #[test]
+    fn different_seeds_diverge_immediately() {
+        let mut params_a = Params::default();
+        params_a.seed = Some(3);
+        let mut params_b = params_a.clone();
+        params_b.seed = Some(4);
+
+        let divergence = compare_runs(&params_a, &params_b, 200).unwrap();
+        assert_eq!(divergence.tick, 0);
+        assert_eq!(divergence.agent, Some(0));
+        assert!(divergence.plant_grid_differs);
+        assert!(divergence.cell.is_some());
+    }
+
+    #[test]
+    fn trace_lines_round_trip() {
+        let hashes = TickHashes {
+            world: 1,
+            plant_grid: 0xffff_ffff_ffff_ffff,
+            agents: vec![3, 4],
+        };
+        let (tick, parsed) = TickHashes::from_line(&hashes.to_line(12)).unwrap();
+        assert_eq!(tick, 12);
+        assert!(parsed == hashes);
+    }
+
+    #[test]
+    fn traces_are_compared_by_tick() {
+        let hashes = |world: u64| TickHashes {
+            world,
+            plant_grid: 0,
+            agents: vec![],
+        };
+        let trace_a = vec![(0, hashes(1)), (1, hashes(2)), (2, hashes(3))];
+        let trace_b = vec![(0, hashes(1)), (1, hashes(2)), (2, hashes(4))];
+        assert_eq!(compare_traces(&trace_a, &trace_b).unwrap().unwrap().tick, 2);
+        assert!(compare_traces(&trace_a, &trace_a[..2]).unwrap().is_none());
+
+        // The same hashes recorded from a later start are not compared as if they matched.
+        let shifted = vec![(1, hashes(1)), (2, hashes(2)), (3, hashes(3))];
+        assert!(compare_traces(&trace_a, &shifted).is_err());
+    }
+}
@@ -0,0 +1,2 @@
+pub mod divergence;
+pub mod sweep;
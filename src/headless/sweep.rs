@@ -0,0 +1,324 @@
+use crate::util::{read_settings_file, split_setting};
+use crate::world::{run_until_end, EndReason, Gene, Params, World};
+use rayon::prelude::*;
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use strum::{EnumCount, IntoEnumIterator};
+
+/// A batch of headless runs: every combination of parameter values, for every seed.
+pub struct Sweep {
+    /// The parameters shared by all runs.
+    pub base: Params,
+    pub seeds: Vec<u64>,
+    /// The swept parameters, each with the values to try.
+    pub axes: Vec<(String, Vec<String>)>,
+}
+
+/// A single run of a sweep.
+pub struct Run {
+    pub seed: u64,
+    /// The value of every swept parameter, in the order of `Sweep::axes`.
+    pub values: Vec<String>,
+    pub params: Params,
+}
+
+/// The outcome of a single run.
+pub struct RunSummary {
+    pub end_reason: EndReason,
+    /// The first time at which all agents were dead.
+    pub extinction_time: Option<f64>,
+    pub final_time: f64,
+    pub final_population: usize,
+    /// The highest generation of any agent, dead or alive.
+    pub max_generation: u32,
+    pub births: u64,
+    pub deaths: u64,
+    pub immigrants: u64,
+    /// The average value of every gene over the final population, indexed by `Gene as usize`.
+    pub gene_averages: Option<[f32; Gene::COUNT]>,
+}
+
+impl Sweep {
+    /// Creates a Sweep of a single run, that lasts until all agents are dead,
+    /// or for 1000 simulated seconds.
+    pub fn new() -> Sweep {
+        let mut base = Params::default();
+        base.end.extinction = true;
+        base.end.time = Some(1000.0);
+        Sweep {
+            base,
+            seeds: vec![1],
+            axes: Vec::new(),
+        }
+    }
+
+    /// Sets a single sweep setting by name, parsing the value from a string.
+    /// `seeds` is a list like `1, 2, 5` or a range like `1..10`, which excludes the end.
+    /// Keys like `sweep.<parameter>` take a list of parameter values to try.
+    /// Any other key sets a parameter for all runs.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let parse_seed = |seed: &str| {
+            seed.trim()
+                .parse::<u64>()
+                .map_err(|_| format!("{} is not a valid seed", seed))
+        };
+
+        match key {
+            "seeds" => {
+                self.seeds = match value.find("..") {
+                    Some(idx) => {
+                        (parse_seed(&value[..idx])?..parse_seed(&value[idx + 2..])?).collect()
+                    }
+                    None => value.split(',').map(parse_seed).collect::<Result<_, _>>()?,
+                };
+                if self.seeds.is_empty() {
+                    return Err("seeds must not be empty".to_string());
+                }
+            }
+            _ if key.starts_with("sweep.") => {
+                let values: Vec<String> = value.split(',').map(|v| v.trim().to_string()).collect();
+                let param = &key["sweep.".len()..];
+                // Fail early on invalid values, instead of halfway through the sweep.
+                for value in values.iter() {
+                    self.base.clone().set(param, value)?;
+                }
+                self.axes.push((param.to_string(), values));
+            }
+            _ => self.base.set(key, value)?,
+        }
+
+        Ok(())
+    }
+
+    /// Applies a list of `key=value` settings.
+    pub fn apply_overrides(&mut self, overrides: &[String]) -> Result<(), String> {
+        for arg in overrides {
+            let (key, value) = split_setting(arg)?;
+            self.set(key, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads a sweep specification with one `key = value` setting per line.
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn load(&mut self, path: &str) -> Result<(), String> {
+        read_settings_file(path, |key, value| self.set(key, value))
+    }
+
+    /// Expands the sweep into runs: every combination of swept values, for every seed.
+    pub fn runs(&self) -> Result<Vec<Run>, String> {
+        let mut combinations: Vec<Vec<String>> = vec![Vec::new()];
+        for (_, values) in self.axes.iter() {
+            combinations = combinations
+                .iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push(value.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+
+        let mut runs = Vec::new();
+        for values in combinations {
+            let mut params = self.base.clone();
+            for ((key, _), value) in self.axes.iter().zip(values.iter()) {
+                params.set(key, value)?;
+            }
+            params.genes.validate()?;
+            if !params.end.any() {
+                return Err("a sweep needs at least one end condition".to_string());
+            }
+            for seed in self.seeds.iter() {
+                let mut params = params.clone();
+                params.seed = Some(*seed);
+                runs.push(Run {
+                    seed: *seed,
+                    values: values.clone(),
+                    params,
+                });
+            }
+        }
+
+        Ok(runs)
+    }
+}
+
+impl Default for Sweep {
+    fn default() -> Sweep {
+        Sweep::new()
+    }
+}
+
+/// Runs a world until one of the end conditions is met, and summarizes the outcome.
+/// Never returns if no end condition is enabled.
+pub fn run(params: &Params) -> RunSummary {
+    let seed = params.seed.unwrap_or(0);
+    let mut world = World::new_seeded(params, seed);
+    world.log_records = false;
+    let mut extinction_time = None;
+    let end_reason = run_until_end(&mut world, params, |world| {
+        if extinction_time.is_none() && world.statistics.extinctions > 0 {
+            extinction_time = Some(world.time);
+        }
+        Ok::<(), Infallible>(())
+    });
+    let end_reason = match end_reason {
+        Ok(reason) => reason,
+        Err(never) => match never {},
+    };
+
+    let gene_averages = if world.agents.is_empty() {
+        None
+    } else {
+        let mut averages = [0.0; Gene::COUNT];
+        for gene in Gene::iter() {
+            let total: f32 = world.agents.iter().map(|agent| agent.genes.get(gene)).sum();
+            averages[gene as usize] = total / world.agents.len() as f32;
+        }
+        Some(averages)
+    };
+    let max_generation = world
+        .agents
+        .iter()
+        .map(|agent| agent.generation)
+        .fold(world.statistics.max_generation, u32::max);
+
+    RunSummary {
+        end_reason,
+        extinction_time,
+        final_time: world.time,
+        final_population: world.agents.len(),
+        max_generation,
+        births: world.statistics.births,
+        deaths: world.statistics.deaths,
+        immigrants: world.statistics.immigrants,
+        gene_averages,
+    }
+}
+
+/// Executes all runs of a sweep in parallel and writes one row per run to a CSV results table.
+pub fn run_sweep(sweep: &Sweep, path: &str) -> Result<(), String> {
+    let runs = sweep.runs()?;
+    let file = File::create(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut writer = BufWriter::new(file);
+    let write_err = |err: std::io::Error| format!("{}: {}", path, err);
+
+    println!("running {} runs", runs.len());
+    let summaries: Vec<RunSummary> = runs
+        .par_iter()
+        .map(|run_spec| {
+            let summary = run(&run_spec.params);
+            let mut label = format!("seed {}", run_spec.seed);
+            for ((key, _), value) in sweep.axes.iter().zip(run_spec.values.iter()) {
+                label.push_str(&format!(", {}={}", key, value));
+            }
+            println!("finished {}", label);
+            summary
+        })
+        .collect();
+
+    let mut header = vec!["seed".to_string()];
+    header.extend(sweep.axes.iter().map(|(key, _)| key.clone()));
+    header.extend(
+        [
+            "end_reason",
+            "extinction_time",
+            "final_time",
+            "final_population",
+            "max_generation",
+            "births",
+            "deaths",
+            "immigrants",
+        ]
+        .iter()
+        .map(|column| column.to_string()),
+    );
+    header.extend(Gene::iter().map(|gene| format!("average_{}", gene.as_ref())));
+    writeln!(writer, "{}", header.join(",")).map_err(write_err)?;
+
+    for (run_spec, summary) in runs.iter().zip(summaries.iter()) {
+        let mut row = vec![run_spec.seed.to_string()];
+        row.extend(run_spec.values.iter().cloned());
+        row.push(summary.end_reason.to_string());
+        row.push(
+            summary
+                .extinction_time
+                .map_or(String::new(), |time| time.to_string()),
+        );
+        row.push(summary.final_time.to_string());
+        row.push(summary.final_population.to_string());
+        row.push(summary.max_generation.to_string());
+        row.push(summary.births.to_string());
+        row.push(summary.deaths.to_string());
+        row.push(summary.immigrants.to_string());
+        for gene in Gene::iter() {
+            row.push(summary.gene_averages.map_or(String::new(), |averages| {
+                averages[gene as usize].to_string()
+            }));
+        }
+        writeln!(writer, "{}", row.join(",")).map_err(write_err)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Immigration;
+
+    #[test]
+    fn sweep_expands_to_every_combination() {
+        let mut sweep = Sweep::new();
+        let overrides: Vec<String> = vec![
+            "seeds = 3..5",
+            "agent_count = 7",
+            "sweep.topology = torus, walled",
+            "sweep.gene.size.max = 5, 6, 7",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        sweep.apply_overrides(&overrides).unwrap();
+
+        let runs = sweep.runs().unwrap();
+
+        assert_eq!(runs.len(), 12);
+        assert_eq!(runs[0].seed, 3);
+        assert_eq!(runs[1].seed, 4);
+        assert_eq!(runs[11].values, vec!["walled", "7"]);
+        assert_eq!(runs[11].params.seed, Some(4));
+        assert_eq!(runs[11].params.agent_count, 7);
+        assert_eq!(runs[11].params.genes.get(Gene::Size).max, 7.0);
+        assert!(sweep.set("sweep.topology", "torus, sphere").is_err());
+        assert!(sweep.set("seeds", "3..3").is_err());
+        sweep.set("end.time", "none").unwrap();
+        sweep.set("end.extinction", "false").unwrap();
+        assert!(sweep.runs().is_err());
+    }
+
+    #[test]
+    fn runs_stop_at_extinction() {
+        let mut params = Sweep::new().base;
+        params.seed = Some(1);
+        params.immigration = Immigration::None;
+        // Agents die of old age before they can reproduce.
+        params.genes.set("lifespan", "min", "300").unwrap();
+        params.genes.set("lifespan", "max", "400").unwrap();
+        params.genes.set("fertility", "min", "0.05").unwrap();
+        params.genes.set("fertility", "max", "0.1").unwrap();
+
+        let summary = run(&params);
+
+        assert_eq!(summary.end_reason, EndReason::Extinction);
+        assert!(summary.extinction_time.unwrap() <= 400.5);
+        assert_eq!(summary.final_time, summary.extinction_time.unwrap());
+        assert_eq!(summary.final_population, 0);
+        assert!(summary.gene_averages.is_none());
+    }
+}
@@ -1,19 +1,166 @@
 pub mod gfx;
+pub mod headless;
 pub mod util;
 pub mod world;
 
+use gfx::image::{fit_view, Sprites};
+use gfx::recording::{Recording, RecordingOptions};
 use gfx::window::main_loop;
+use headless::divergence;
+use headless::sweep::{self, Sweep};
 use std::env;
-use world::Params;
+use util::time_ns;
+use world::{run_until_end, Params, World};
 
-fn main() {
-    let args: Vec<_> = env::args().collect();
+const USAGE: &str = "usage:
+  mini-universe [seed] [key=value...] [record.<option>=value...]
+  mini-universe trace <file> <ticks> [key=value...]
+  mini-universe diverge <ticks> [key=value...] -- [key=value...]
+  mini-universe diverge-traces <file a> <file b>
+  mini-universe run [key=value...] [record.<option>=value...]
+  mini-universe sweep <sweep file> <results file> [key=value...]";
+
+/// Parses a command-line argument, or returns an error describing what was expected.
+fn parse_arg<T: std::str::FromStr>(args: &[String], idx: usize, name: &str) -> Result<T, String> {
+    args.get(idx)
+        .ok_or_else(|| format!("missing argument: {}", name))?
+        .parse::<T>()
+        .map_err(|_| format!("invalid {}: {}", name, args[idx]))
+}
+
+/// Parses `key=value` parameter overrides on top of the default parameters.
+/// Without a seed override, a random seed is chosen, so it can be shared between runs.
+fn parse_params(overrides: &[String]) -> Result<Params, String> {
     let mut params = Params::default();
-    if args.len() > 1 {
-        params.seed = Some(args[1].parse::<u64>().unwrap());
-    } else {
-        params.seed = None;
+    params.apply_overrides(overrides)?;
+    if params.seed.is_none() {
+        let seed = time_ns() as u64 % 10_000_000;
+        println!("using seed: {}", seed);
+        params.seed = Some(seed);
+    }
+    Ok(params)
+}
+
+/// Splits off the `record.<option>=value` arguments and parses them into recording options,
+/// which are `None` without any such argument.
+fn parse_recording_options(
+    args: &[String],
+) -> Result<(Option<RecordingOptions>, Vec<String>), String> {
+    let (record_args, other_args): (Vec<String>, Vec<String>) = args
+        .iter()
+        .cloned()
+        .partition(|arg| arg.starts_with("record."));
+    if record_args.is_empty() {
+        return Ok((None, other_args));
     }
 
-    main_loop(&params);
+    let mut options = RecordingOptions::new();
+    for arg in record_args.iter() {
+        let option = &arg["record.".len()..];
+        match option.find('=') {
+            Some(idx) => options.set(option[..idx].trim(), option[idx + 1..].trim())?,
+            None => return Err(format!("expected key=value, got: {}", arg)),
+        }
+    }
+    Ok((Some(options), other_args))
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("trace") => {
+            let path: String = parse_arg(args, 2, "file")?;
+            let ticks = parse_arg(args, 3, "ticks")?;
+            let params = parse_params(&args[4..])?;
+            divergence::record_trace(&params, ticks, &path)
+        }
+        Some("diverge") => {
+            let ticks = parse_arg(args, 2, "ticks")?;
+            let mut configs = args[3..].split(|arg| arg == "--");
+            let params_a = parse_params(configs.next().unwrap_or(&[]))?;
+            let mut params_b = params_a.clone();
+            params_b.apply_overrides(configs.next().unwrap_or(&[]))?;
+            match divergence::compare_runs(&params_a, &params_b, ticks) {
+                Some(divergence) => print!("{}", divergence),
+                None => println!("runs are identical for {} ticks", ticks),
+            }
+            Ok(())
+        }
+        Some("diverge-traces") => {
+            let trace_a = divergence::read_trace(&parse_arg::<String>(args, 2, "file a")?)?;
+            let trace_b = divergence::read_trace(&parse_arg::<String>(args, 3, "file b")?)?;
+            match divergence::compare_traces(&trace_a, &trace_b)? {
+                Some(divergence) => print!("{}", divergence),
+                None => println!(
+                    "traces are identical for {} ticks",
+                    trace_a.len().min(trace_b.len())
+                ),
+            }
+            Ok(())
+        }
+        Some("run") => {
+            let (recording_options, param_args) = parse_recording_options(&args[2..])?;
+            let params = parse_params(&param_args)?;
+            if !params.end.any() {
+                return Err("no end condition set, for example end.time=1000".to_string());
+            }
+
+            let mut world = World::new(&params);
+            let mut recording = match recording_options {
+                Some(options) => {
+                    let recording =
+                        Recording::start(&options, options.size, &world, Sprites::load()?)?;
+                    println!("recording to {}", recording.path().display());
+                    Some(recording)
+                }
+                None => None,
+            };
+            let reason = run_until_end(&mut world, &params, |world| match recording.as_mut() {
+                Some(recording) => recording.update(&fit_view(recording.size(), world), world),
+                None => Ok(()),
+            })?;
+            if let Some(recording) = recording {
+                println!("recorded {} frames", recording.frames());
+                recording.finish()?;
+            }
+            println!("[{}] run ended: {}", world.time.floor(), reason);
+            println!(
+                "agents: {}, births: {}, deaths: {}, immigrants: {}, max generation: {}",
+                world.agents.len(),
+                world.statistics.births,
+                world.statistics.deaths,
+                world.statistics.immigrants,
+                world.statistics.max_generation
+            );
+            Ok(())
+        }
+        Some("sweep") => {
+            let mut sweep = Sweep::new();
+            sweep.load(&parse_arg::<String>(args, 2, "sweep file")?)?;
+            sweep.apply_overrides(&args[4.min(args.len())..])?;
+            sweep::run_sweep(&sweep, &parse_arg::<String>(args, 3, "results file")?)
+        }
+        _ => {
+            let mut params = Params::default();
+            let mut overrides = &args[1..];
+            if args.len() > 1 && !args[1].contains('=') {
+                params.seed = Some(parse_arg(args, 1, "seed")?);
+                overrides = &args[2..];
+            } else {
+                params.seed = None;
+            }
+            let (recording_options, overrides) = parse_recording_options(overrides)?;
+            params.apply_overrides(&overrides)?;
+
+            main_loop(&params, &recording_options.unwrap_or_default());
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if let Err(err) = run(&args) {
+        eprintln!("{}\n{}", err, USAGE);
+        std::process::exit(1);
+    }
 }
@@ -1,4 +1,8 @@
+use rand::Rng;
 use rand_pcg;
+use std::fs;
+use std::hash::Hasher;
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 use vek;
 
@@ -42,6 +46,116 @@ pub fn calculate_vec2f(speed: f32, angle: f32) -> Vec2f {
     Vec2f::new(angle.cos() * speed, angle.sin() * speed)
 }
 
+/// Parse the value of a setting, with an error message that names the setting.
+pub fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("{} is not a valid value for {}", value, key))
+}
+
+/// Parse the value of a setting that must be greater than zero.
+pub fn parse_positive_value<T: FromStr + PartialOrd + Default>(
+    key: &str,
+    value: &str,
+) -> Result<T, String> {
+    match parse_value(key, value)? {
+        number if number > T::default() => Ok(number),
+        _ => Err(format!("{} must be positive", key)),
+    }
+}
+
+/// Split a `key=value` setting into its key and value.
+pub fn split_setting(setting: &str) -> Result<(&str, &str), String> {
+    match setting.find('=') {
+        Some(idx) => Ok((setting[..idx].trim(), setting[idx + 1..].trim())),
+        None => Err(format!("expected key=value, got: {}", setting)),
+    }
+}
+
+/// Read a file with one `key = value` setting per line, and pass every setting to `set`.
+/// Empty lines and lines starting with `#` are ignored.
+/// Errors are prefixed with the path and line number.
+pub fn read_settings_file(
+    path: &str,
+    mut set: impl FnMut(&str, &str) -> Result<(), String>,
+) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    for (line_idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        split_setting(line)
+            .and_then(|(key, value)| set(key, value))
+            .map_err(|err| format!("{}: line {}: {}", path, line_idx + 1, err))?;
+    }
+
+    Ok(())
+}
+
+/// A section of a file, that starts with a header line and is followed by `key = value` lines.
+pub trait Section: Sized {
+    type Output;
+    const HEADER: &'static str;
+
+    fn new() -> Self;
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String>;
+    fn finish(self) -> Result<Self::Output, String>;
+}
+
+/// Parse a file consisting of sections of one type.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse_sections<S: Section>(contents: &str) -> Result<Vec<S::Output>, String> {
+    // Errors of a finished section are reported at the line of its header.
+    let finish = |section: S, header_line: usize| {
+        section
+            .finish()
+            .map_err(|err| format!("line {}: {}", header_line, err))
+    };
+
+    let mut items = Vec::new();
+    let mut current: Option<(S, usize)> = None;
+    for (line_idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let with_line = |err: String| format!("line {}: {}", line_idx + 1, err);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line == S::HEADER {
+            if let Some((section, header_line)) = current.take() {
+                items.push(finish(section, header_line)?);
+            }
+            current = Some((S::new(), line_idx + 1));
+            continue;
+        }
+
+        let (section, _) = current
+            .as_mut()
+            .ok_or_else(|| with_line(format!("expected {}, got: {}", S::HEADER, line)))?;
+        split_setting(line)
+            .and_then(|(key, value)| section.set(key, value))
+            .map_err(with_line)?;
+    }
+    if let Some((section, header_line)) = current {
+        items.push(finish(section, header_line)?);
+    }
+
+    Ok(items)
+}
+
+/// Read a file consisting of sections of one type. The file must contain at least one section.
+pub fn load_sections<S: Section>(path: &str) -> Result<Vec<S::Output>, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let items = parse_sections::<S>(&contents).map_err(|err| format!("{}: {}", path, err))?;
+    if items.is_empty() {
+        return Err(format!("{}: no {} sections found", path, S::HEADER));
+    }
+
+    Ok(items)
+}
+
 /// Get the current Unix time in nanoseconds.
 pub fn time_ns() -> u128 {
     let time_since_epoch = SystemTime::now()
@@ -49,3 +163,43 @@ pub fn time_ns() -> u128 {
         .expect("Time went backwards");
     time_since_epoch.as_nanos()
 }
+
+/// 64-bit FNV-1a hasher, used for world state hashes.
+/// Unlike `DefaultHasher`, its output is stable across Rust versions,
+/// so hashes can be recorded and compared between builds.
+pub struct FnvHasher(u64);
+
+impl FnvHasher {
+    pub fn new() -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    #[inline]
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+}
+
+impl Default for FnvHasher {
+    fn default() -> FnvHasher {
+        FnvHasher::new()
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hashes the state of a random number generator without advancing it.
+pub fn hash_rng(rng: &WRng, hasher: &mut FnvHasher) {
+    hasher.write_u64(rng.clone().gen::<u64>());
+}
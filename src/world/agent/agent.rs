@@ -1,10 +1,13 @@
-use crate::util::{calculate_vec2f, vec2f_to_vec2i, Rect2f, Vec2f, WRng};
-use crate::world::agent::brain::{Brain, Command, Commands, Percept, Percepts};
+use crate::util::{calculate_vec2f, hash_rng, FnvHasher, Rect2f, Vec2f, Vec2i, WRng};
+use crate::world::agent::brain::{Brain, Command, Commands, Percept, Percepts, Weights};
 use crate::world::agent::genes::Genes;
+use crate::world::agent::genome::Genome;
 use crate::world::params::Params;
 use crate::world::plant_grid::PlantGrid;
-use rand::Rng;
-use strum::EnumCount;
+use crate::world::plant_species::PlantSpecies;
+use rand::{Rng, SeedableRng};
+use std::hash::Hasher;
+use strum::{EnumCount, IntoEnumIterator};
 use vek::ops::{Clamp, Lerp};
 
 const MOUTH_DISTANCE: f32 = 2.0;
@@ -12,14 +15,28 @@ const MOUTH_DISTANCE: f32 = 2.0;
 const ENERGY_LOSE_SPEED: f32 = 0.02;
 const ENERGY_EAT_GAIN: f32 = 0.1;
 
+/// The reproduction interval of an agent with a fertility of 1.0.
 const REPRODUCE_INTERVAL: f32 = 100.0;
 const REPRODUCE_ENERGY_COST: f32 = 0.5;
 
 const INITIAL_SIZE_FACTOR: f32 = 0.2;
 const TIME_UNTIL_GROWN: f32 = 5.0;
 
+/// How the first agent of a lineage came into the world.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Origin {
+    /// Part of the initial population.
+    Founder,
+    /// Injected later on by immigration.
+    Immigrant,
+}
+
 /// A living creature with genes and a brain.
 pub struct Agent {
+    /// Identifies the agent while it lives. Assigned by the world when the agent is added.
+    pub id: u64,
+    /// The id of the agent's parent, or `None` if it started a lineage.
+    pub parent: Option<u64>,
     pub genes: Genes,
     pub pos: Vec2f,
     pub angle: f32, // radians
@@ -27,50 +44,117 @@ pub struct Agent {
     pub generation: u32,
     pub time_alive: f32,
     pub time_since_reproduce: f32,
+    /// Identifies the first agent of the lineage. Inherited by offspring.
+    pub lineage: u32,
+    /// The origin of the first agent of the lineage. Inherited by offspring.
+    pub origin: Origin,
     brain: Brain,
+    /// The agent's own random stream, so its randomness doesn't depend on tick order.
+    rng: WRng,
 }
 
 pub struct TickResult {
-    pub eat: bool,
+    /// Which plant species the agent ate, indexed by `PlantSpecies as usize`.
+    pub eat: [bool; PlantSpecies::COUNT],
+    /// The energy the agent gained by eating. Negative if it ate poison.
+    pub energy_eaten: f32,
     pub die: bool,
     pub reproduce: bool,
 }
 
 impl Agent {
-    /// Generates a random agent.
-    pub fn new_random(params: &Params, rng: &mut WRng) -> Agent {
-        let pos = Vec2f::new(
-            rng.gen::<f32>() * params.plant_grid_size.w as f32,
-            rng.gen::<f32>() * params.plant_grid_size.h as f32,
-        );
-        let angle = rng.gen::<f32>() * std::f32::consts::PI * 2.0;
+    /// Generates a random agent that starts a new lineage.
+    pub fn new_random(params: &Params, origin: Origin, lineage: u32, rng: &mut WRng) -> Agent {
+        let (pos, angle) = random_placement(params, rng);
         Agent {
-            genes: Genes::new_random(rng),
+            id: 0,
+            parent: None,
+            genes: Genes::new_random(&params.genes, rng),
             pos,
             angle,
             energy: 1.0,
             generation: 1,
             time_alive: 0.0,
             time_since_reproduce: 0.0,
+            lineage,
+            origin,
             brain: Brain::new_random(rng),
+            rng: WRng::seed_from_u64(rng.gen()),
+        }
+    }
+
+    /// Generates an agent from an imported genome at a random position.
+    /// It starts a new lineage, but continues the generation count of the genome.
+    pub fn from_genome(
+        genome: &Genome,
+        params: &Params,
+        origin: Origin,
+        lineage: u32,
+        rng: &mut WRng,
+    ) -> Agent {
+        let (pos, angle) = random_placement(params, rng);
+        Agent {
+            id: 0,
+            parent: None,
+            genes: Genes::from_values(&genome.genes, &params.genes),
+            pos,
+            angle,
+            energy: 1.0,
+            generation: genome.generation,
+            time_alive: 0.0,
+            time_since_reproduce: 0.0,
+            lineage,
+            origin,
+            brain: Brain::from_weights(&genome.weights),
+            rng: WRng::seed_from_u64(rng.gen()),
         }
     }
 
     /// Reproduces the agent asexually, mutating according to the mutation factor.
-    pub fn reproduce(&self, rng: &mut WRng) -> Agent {
+    /// Uses the agent's own random stream.
+    pub fn reproduce(&mut self, params: &Params) -> Agent {
         let mutation_factor = self.genes.get_mutation_factor();
+        let rng = &mut self.rng;
         Agent {
-            genes: self.genes.reproduce(rng),
+            id: 0,
+            parent: Some(self.id),
+            genes: self.genes.reproduce(&params.genes, rng),
             pos: self.pos,
             angle: std::f32::consts::PI + self.angle,
             energy: 1.0,
             generation: self.generation + 1,
             time_alive: 0.0,
             time_since_reproduce: 0.0,
+            lineage: self.lineage,
+            origin: self.origin,
             brain: self.brain.reproduce(mutation_factor, rng),
+            rng: WRng::seed_from_u64(rng.gen()),
         }
     }
 
+    /// Hashes the complete state of the agent, including its genes, brain and random stream.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = FnvHasher::new();
+        self.genes.hash_state(&mut hasher);
+        hasher.write_f32(self.pos.x);
+        hasher.write_f32(self.pos.y);
+        hasher.write_f32(self.angle);
+        hasher.write_f32(self.energy);
+        hasher.write_u32(self.generation);
+        hasher.write_f32(self.time_alive);
+        hasher.write_f32(self.time_since_reproduce);
+        hasher.write_u32(self.lineage);
+        hasher.write_u8(self.origin as u8);
+        self.brain.hash_state(&mut hasher);
+        hash_rng(&self.rng, &mut hasher);
+
+        hasher.finish()
+    }
+
+    pub fn brain_weights(&self) -> &Weights {
+        self.brain.weights()
+    }
+
     fn get_size(&self) -> f32 {
         let max_size = self.genes.get_size();
         Lerp::lerp(INITIAL_SIZE_FACTOR * max_size, max_size, self.time_alive / TIME_UNTIL_GROWN)
@@ -100,15 +184,30 @@ impl Agent {
         self.pos + calculate_vec2f(MOUTH_DISTANCE, self.angle)
     }
 
+    /// Returns what the agent currently senses and the commands its brain sends in response,
+    /// which it will act on in the next tick.
+    pub fn percepts_and_commands(&self, plant_grid: &PlantGrid) -> (Percepts, Commands) {
+        let percepts = self.measure_sensors(plant_grid);
+        let commands = self.brain.run(&percepts);
+        (percepts, commands)
+    }
+
     /// Measures the surrounding world using the sensors.
     fn measure_sensors(&self, plant_grid: &PlantGrid) -> Percepts {
-        let left_density = plant_grid.get_density(vec2f_to_vec2i(self.get_left_measure_pos()));
-        let right_density = plant_grid.get_density(vec2f_to_vec2i(self.get_right_measure_pos()));
+        let left_cell = plant_grid.cell_at(self.get_left_measure_pos());
+        let right_cell = plant_grid.cell_at(self.get_right_measure_pos());
+        let density_at = |species, cell: Option<Vec2i>| {
+            cell.map_or(0, |cell| plant_grid.get_density_unchecked(species, cell))
+        };
 
         let mut result: Percepts = [0.0; Percept::COUNT];
         result[Percept::ConstantOne as usize] = 1.0;
-        result[Percept::LeftEye as usize] = left_density as f32 / 255.0;
-        result[Percept::RightEye as usize] = right_density as f32 / 255.0;
+        for species in PlantSpecies::iter() {
+            result[Percept::left_eye(species) as usize] =
+                density_at(species, left_cell) as f32 / 255.0;
+            result[Percept::right_eye(species) as usize] =
+                density_at(species, right_cell) as f32 / 255.0;
+        }
         result[Percept::TimeWave as usize] =
             ((self.time_alive / self.genes.get_timer_interval()) * std::f32::consts::PI * 2.0)
                 .sin();
@@ -117,7 +216,7 @@ impl Agent {
     }
 
     /// Applies the commands to the actuators, i.e. makes the agent move based on the brain output.
-    /// Returns whether the agent moved forward.
+    /// Returns whether the agent moved forward. An agent that runs into a wall only turns.
     fn apply_actuators(
         &mut self,
         commands: &Commands,
@@ -133,50 +232,85 @@ impl Agent {
         let radial_speed = (1.0 / radius) * left_speed - (1.0 / radius) * right_speed;
 
         self.angle += radial_speed * d_time;
-        self.pos += calculate_vec2f(speed, self.angle) * d_time;
-
-        if self.pos.x < 0.0 {
-            self.pos.x = plant_grid.size.w as f32;
-        } else if self.pos.y < 0.0 {
-            self.pos.y = plant_grid.size.h as f32;
-        } else if self.pos.x > plant_grid.size.w as f32 {
-            self.pos.x = 0.0
-        } else if self.pos.y > plant_grid.size.h as f32 {
-            self.pos.y = 0.0;
+        let new_pos = plant_grid.wrap_pos(self.pos + calculate_vec2f(speed, self.angle) * d_time);
+        // Agents can't enter walls, but an agent inside a wall can leave it.
+        if plant_grid.is_wall_at(new_pos) && !plant_grid.is_wall_at(self.pos) {
+            return false;
         }
+        self.pos = new_pos;
 
         speed > 0.0
     }
 
-    /// Updates the agent for the specified amount of time.
-    /// Returns whether the agent should eat, die and/or reproduce.
-    pub fn tick(&mut self, plant_grid: &PlantGrid, d_time: f32) -> TickResult {
+    /// First phase of a tick: senses the world, thinks and moves.
+    /// Only modifies the agent itself, so all agents can run this phase in parallel.
+    /// Returns whether the agent moved forward.
+    pub fn sense_and_move(&mut self, plant_grid: &PlantGrid, d_time: f32) -> bool {
         let percepts = self.measure_sensors(plant_grid);
         let commands = self.brain.run(&percepts);
-        let moved_forward = self.apply_actuators(&commands, plant_grid, d_time);
+        self.apply_actuators(&commands, plant_grid, d_time)
+    }
 
-        let density_at_mouth = plant_grid.get_density(vec2f_to_vec2i(self.get_mouth_pos()));
-        let eat = moved_forward && density_at_mouth > 0;
+    /// Second phase of a tick: eats, ages and decides whether to die and/or reproduce.
+    /// Must be run in a fixed agent order, because eating depends on what other agents ate.
+    pub fn resolve(
+        &mut self,
+        moved_forward: bool,
+        plant_grid: &PlantGrid,
+        d_time: f32,
+    ) -> TickResult {
+        let mouth_cell = plant_grid.cell_at(self.get_mouth_pos());
+        let mut eat = [false; PlantSpecies::COUNT];
 
         self.energy -= ENERGY_LOSE_SPEED * d_time;
-        if eat {
-            self.energy += ENERGY_EAT_GAIN * (density_at_mouth as f32 / 255.0);
-            self.energy = self.energy.clamped(0.0, 1.0);
+        let energy_before_eating = self.energy;
+        if let Some(mouth_cell) = mouth_cell.filter(|_| moved_forward) {
+            for species in PlantSpecies::iter() {
+                let density_at_mouth = plant_grid.get_density_unchecked(species, mouth_cell);
+                if density_at_mouth == 0 {
+                    continue;
+                }
+                eat[species as usize] = true;
+                // A specialized diet digests its species better than an even spread would.
+                let digestion = self.genes.get_diet(species) * PlantSpecies::COUNT as f32;
+                self.energy += ENERGY_EAT_GAIN
+                    * species.traits().nutrition
+                    * digestion
+                    * (density_at_mouth as f32 / 255.0);
+                self.energy = self.energy.clamped(0.0, 1.0);
+            }
         }
+        let energy_eaten = self.energy - energy_before_eating;
 
         self.time_alive += d_time;
         self.time_since_reproduce += d_time;
 
-        let reproduce = self.time_since_reproduce > REPRODUCE_INTERVAL;
+        let reproduce = self.time_since_reproduce > REPRODUCE_INTERVAL / self.genes.get_fertility();
         if reproduce {
             self.time_since_reproduce = 0.0;
             self.energy -= REPRODUCE_ENERGY_COST;
         }
 
+        let old_age = match self.genes.get_lifespan() {
+            Some(lifespan) => self.time_alive > lifespan,
+            None => false,
+        };
+
         TickResult {
             eat,
-            die: self.energy <= 0.0,
+            energy_eaten,
+            die: self.energy <= 0.0 || old_age,
             reproduce,
         }
     }
 }
+
+/// Returns a random position and angle in the world.
+fn random_placement(params: &Params, rng: &mut WRng) -> (Vec2f, f32) {
+    let pos = Vec2f::new(
+        rng.gen::<f32>() * params.plant_grid_size.w as f32,
+        rng.gen::<f32>() * params.plant_grid_size.h as f32,
+    );
+    let angle = rng.gen::<f32>() * std::f32::consts::PI * 2.0;
+    (pos, angle)
+}
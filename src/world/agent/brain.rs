@@ -1,18 +1,45 @@
-use crate::util::WRng;
+use crate::util::{FnvHasher, WRng};
+use crate::world::plant_species::PlantSpecies;
 use rand::Rng;
 use strum::EnumCount;
-use strum_macros::{AsRefStr, EnumCount as EnumCountMacro, EnumIter};
+use strum_macros::{AsRefStr, EnumCount as EnumCountMacro, EnumIter, EnumString};
 use vek::ops::Clamp;
 
-#[derive(Copy, Clone, PartialEq, EnumCountMacro, EnumIter, AsRefStr)]
+#[derive(Copy, Clone, PartialEq, EnumCountMacro, EnumIter, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum Percept {
     ConstantOne = 0,
-    LeftEye = 1,
-    RightEye = 2,
-    TimeWave = 3,
+    LeftEyeGrass = 1,
+    LeftEyeBerries = 2,
+    LeftEyePoison = 3,
+    RightEyeGrass = 4,
+    RightEyeBerries = 5,
+    RightEyePoison = 6,
+    TimeWave = 7,
 }
 
-#[derive(Copy, Clone, PartialEq, EnumCountMacro, EnumIter, AsRefStr)]
+impl Percept {
+    /// The left eye percept for the given plant species.
+    pub fn left_eye(species: PlantSpecies) -> Percept {
+        match species {
+            PlantSpecies::Grass => Percept::LeftEyeGrass,
+            PlantSpecies::Berries => Percept::LeftEyeBerries,
+            PlantSpecies::Poison => Percept::LeftEyePoison,
+        }
+    }
+
+    /// The right eye percept for the given plant species.
+    pub fn right_eye(species: PlantSpecies) -> Percept {
+        match species {
+            PlantSpecies::Grass => Percept::RightEyeGrass,
+            PlantSpecies::Berries => Percept::RightEyeBerries,
+            PlantSpecies::Poison => Percept::RightEyePoison,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, EnumCountMacro, EnumIter, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum Command {
     LeftTrack = 0,
     RightTrack = 1,
@@ -20,13 +47,16 @@ pub enum Command {
 
 pub type Percepts = [f32; Percept::COUNT];
 pub type Commands = [f32; Command::COUNT];
+/// Brain weights, indexed by `Percept as usize * Command::COUNT + Command as usize`.
+pub type Weights = [f32; Percept::COUNT * Command::COUNT];
 
 /// A brain calculates what commands to send to the actuators,
 /// based on the inputs from the sensors.
 /// Currently uses a simple single-layer feed-forward neural network.
 /// The activation function is an add (+) clamped to [0.0, 1.0].
+#[derive(Clone)]
 pub struct Brain {
-    weights: [f32; Percept::COUNT * Command::COUNT],
+    weights: Weights,
 }
 
 impl Brain {
@@ -47,6 +77,20 @@ impl Brain {
         Brain { weights }
     }
 
+    /// Creates a brain with the given weights, clamped to their valid ranges.
+    pub fn from_weights(weights: &Weights) -> Brain {
+        let mut weights = *weights;
+        for (idx, weight) in weights.iter_mut().enumerate() {
+            *weight = clamp_weight(idx / Command::COUNT, *weight);
+        }
+
+        Brain { weights }
+    }
+
+    pub fn weights(&self) -> &Weights {
+        &self.weights
+    }
+
     /// Reproduces the brain asexually, mutating according to the mutation factor.
     pub fn reproduce(&self, mutation_factor: f32, rng: &mut WRng) -> Brain {
         let mut new_weights = self.weights.clone();
@@ -54,12 +98,7 @@ impl Brain {
             for col in 0..Command::COUNT {
                 let weight = new_weights[row * Command::COUNT + col];
                 let new_weight = weight + (rng.gen::<f32>() * 2.0 - 1.0) * mutation_factor;
-                let clamped_new_weight = if row == 0 {
-                    new_weight.clamped(0.0, 1.0)
-                } else {
-                    new_weight.clamped(-1.0, 1.0)
-                };
-                new_weights[row * Command::COUNT + col] = clamped_new_weight;
+                new_weights[row * Command::COUNT + col] = clamp_weight(row, new_weight);
             }
         }
         Brain {
@@ -67,6 +106,13 @@ impl Brain {
         }
     }
 
+    /// Feeds the weights into a state hash.
+    pub fn hash_state(&self, hasher: &mut FnvHasher) {
+        for weight in self.weights.iter() {
+            hasher.write_f32(*weight);
+        }
+    }
+
     /// Use the brain to calculate what commands to send to the actuators based on the given
     /// percepts from the sensors.
     pub fn run(&self, percepts: &Percepts) -> Commands {
@@ -85,3 +131,12 @@ impl Brain {
         result
     }
 }
+
+/// Clamps a weight of the given percept row to its valid range.
+fn clamp_weight(row: usize, weight: f32) -> f32 {
+    if row == Percept::ConstantOne as usize {
+        weight.clamped(0.0, 1.0)
+    } else {
+        weight.clamped(-1.0, 1.0)
+    }
+}
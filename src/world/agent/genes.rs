@@ -1,10 +1,15 @@
-use crate::util::{Vec3f, WRng};
+use crate::util::{parse_value, FnvHasher, Vec3f, WRng};
+use crate::world::plant_species::PlantSpecies;
 use rand::Rng;
-use strum::EnumCount;
-use strum_macros::{AsRefStr, EnumCount as EnumCountMacro, EnumIter};
-use vek::ops::Lerp;
+use strum::{EnumCount, IntoEnumIterator};
+use strum_macros::{AsRefStr, EnumCount as EnumCountMacro, EnumIter, EnumString};
+use vek::ops::{Clamp, Lerp};
 
-#[derive(Copy, Clone, PartialEq, EnumCountMacro, EnumIter, AsRefStr)]
+/// All genes of an agent.
+/// To add a gene, add a variant here, give it a default specification in
+/// `Gene::default_spec` and add a getter to `Genes`.
+#[derive(Copy, Clone, PartialEq, EnumCountMacro, EnumIter, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum Gene {
     MutationFactor = 0,
     ColorR = 1,
@@ -15,50 +20,222 @@ pub enum Gene {
     EyeDistance = 6,
     EyeAngle = 7,
     TimerInterval = 8,
+    DietGrass = 9,
+    DietBerries = 10,
+    DietPoison = 11,
+    /// The age in seconds at which the agent dies, or 0 for no limit.
+    Lifespan = 12,
+    Fertility = 13,
 }
 
-const MAX_MUTATION_FACTOR: f32 = 0.1;
-const MIN_SIZE: f32 = 2.5;
-const MAX_SIZE: f32 = 5.0;
-const MIN_SPEED: f32 = 5.0;
-const MAX_SPEED: f32 = 10.0;
-const MIN_EYE_DISTANCE: f32 = 4.0;
-const MAX_EYE_DISTANCE: f32 = 10.0;
-const MIN_EYE_ANGLE: f32 = 0.1;
-const MAX_EYE_ANGLE: f32 = 0.5;
-const MIN_TIMER_INTERVAL: f32 = 1.0;
-const MAX_TIMER_INTERVAL: f32 = 50.0;
+/// What happens to a gene that mutates beyond its range.
+#[derive(Copy, Clone, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum GeneBounds {
+    /// The gene stops at the edge of its range.
+    Clamp,
+    /// The gene bounces back from the edge of its range.
+    Reflect,
+}
+
+/// The range and mutation behavior of a single gene.
+#[derive(Copy, Clone)]
+pub struct GeneSpec {
+    pub min: f32,
+    pub max: f32,
+    /// Multiplier for the agent's mutation factor, so some genes can mutate faster than others.
+    pub mutation_rate: f32,
+    pub bounds: GeneBounds,
+}
+
+impl GeneSpec {
+    const fn new(min: f32, max: f32) -> GeneSpec {
+        GeneSpec {
+            min,
+            max,
+            mutation_rate: 1.0,
+            bounds: GeneBounds::Clamp,
+        }
+    }
+
+    /// Moves a value that mutated beyond the range back inside it.
+    fn bound(&self, value: f32) -> f32 {
+        let value = match self.bounds {
+            GeneBounds::Clamp => value,
+            GeneBounds::Reflect if value > self.max => 2.0 * self.max - value,
+            GeneBounds::Reflect if value < self.min => 2.0 * self.min - value,
+            GeneBounds::Reflect => value,
+        };
+
+        // Even a reflected value may still be out of range after a large mutation.
+        value.clamped(self.min, self.max)
+    }
+}
+
+impl Gene {
+    /// The diet gene for the given plant species.
+    pub fn diet(species: PlantSpecies) -> Gene {
+        match species {
+            PlantSpecies::Grass => Gene::DietGrass,
+            PlantSpecies::Berries => Gene::DietBerries,
+            PlantSpecies::Poison => Gene::DietPoison,
+        }
+    }
+
+    /// The specification of the gene, unless configured otherwise.
+    pub fn default_spec(self) -> GeneSpec {
+        match self {
+            Gene::MutationFactor => GeneSpec::new(0.0, 0.1),
+            Gene::ColorR | Gene::ColorG | Gene::ColorB => GeneSpec::new(0.0, 1.0),
+            Gene::Size => GeneSpec::new(2.5, 5.0),
+            Gene::Speed => GeneSpec::new(5.0, 10.0),
+            Gene::EyeDistance => GeneSpec::new(4.0, 10.0),
+            Gene::EyeAngle => GeneSpec::new(0.1, 0.5),
+            Gene::TimerInterval => GeneSpec::new(1.0, 50.0),
+            Gene::DietGrass | Gene::DietBerries | Gene::DietPoison => GeneSpec::new(0.0, 1.0),
+            // Agents live and reproduce as they did before these genes existed, unless configured.
+            Gene::Lifespan => GeneSpec::new(0.0, 0.0),
+            Gene::Fertility => GeneSpec::new(1.0, 1.0),
+        }
+    }
+}
+
+/// The specifications of all genes, indexed by `Gene as usize`.
+#[derive(Clone)]
+pub struct GeneConfig {
+    specs: [GeneSpec; Gene::COUNT],
+}
+
+impl Default for GeneConfig {
+    /// Creates a GeneConfig with the default specification of every gene.
+    fn default() -> GeneConfig {
+        let mut specs = [GeneSpec::new(0.0, 1.0); Gene::COUNT];
+        for gene in Gene::iter() {
+            specs[gene as usize] = gene.default_spec();
+        }
+
+        GeneConfig { specs }
+    }
+}
+
+impl GeneConfig {
+    pub fn get(&self, gene: Gene) -> &GeneSpec {
+        &self.specs[gene as usize]
+    }
+
+    /// Sets a single field of a gene specification by name, parsing the value from a string.
+    /// The gene may be `all` to set the field of every gene.
+    pub fn set(&mut self, gene: &str, field: &str, value: &str) -> Result<(), String> {
+        let key = format!("gene.{}.{}", gene, field);
+        let genes: Vec<Gene> = if gene == "all" {
+            Gene::iter().collect()
+        } else {
+            vec![gene
+                .parse::<Gene>()
+                .map_err(|_| format!("unknown gene: {}", gene))?]
+        };
+        for gene in genes {
+            let spec = &mut self.specs[gene as usize];
+            match field {
+                "min" => spec.min = parse_value(&key, value)?,
+                "max" => spec.max = parse_value(&key, value)?,
+                "mutation_rate" => spec.mutation_rate = parse_value(&key, value)?,
+                "bounds" => spec.bounds = parse_value(&key, value)?,
+                _ => return Err(format!("unknown gene field: {}", field)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every gene has a valid range.
+    /// Agents divide by their size, timer interval and fertility, so these must be positive,
+    /// and a negative lifespan or diet has no meaning.
+    pub fn validate(&self) -> Result<(), String> {
+        for gene in Gene::iter() {
+            let spec = self.get(gene);
+            if spec.min > spec.max {
+                return Err(format!("gene {} has min above max", gene.as_ref()));
+            }
+            match gene {
+                Gene::Size | Gene::TimerInterval | Gene::Fertility if spec.min <= 0.0 => {
+                    return Err(format!("gene {} must be positive", gene.as_ref()));
+                }
+                Gene::Lifespan | Gene::DietGrass | Gene::DietBerries | Gene::DietPoison
+                    if spec.min < 0.0 =>
+                {
+                    return Err(format!("gene {} must not be negative", gene.as_ref()));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
 
 /// Struct that keeps track of an agent's genes.
 /// Has methods for determining agent attributes.
+/// Genes are stored as attribute values, within the ranges of their specifications.
+#[derive(Clone)]
 pub struct Genes {
     genes: [f32; Gene::COUNT],
 }
 
 impl Genes {
-    /// Generates random genes.
-    pub fn new_random(rng: &mut WRng) -> Genes {
+    /// Generates random genes, uniformly distributed over their ranges.
+    pub fn new_random(config: &GeneConfig, rng: &mut WRng) -> Genes {
         let mut genes = [0.0; Gene::COUNT];
 
-        for i in 0..Gene::COUNT {
-            genes[i] = rng.gen::<f32>();
+        for gene in Gene::iter() {
+            let spec = config.get(gene);
+            genes[gene as usize] = Lerp::lerp(spec.min, spec.max, rng.gen::<f32>());
+        }
+
+        Genes { genes }
+    }
+
+    /// Creates genes with the given values, clamped to the ranges of their specifications.
+    pub fn from_values(values: &[f32; Gene::COUNT], config: &GeneConfig) -> Genes {
+        let mut genes = *values;
+        for gene in Gene::iter() {
+            let spec = config.get(gene);
+            genes[gene as usize] = genes[gene as usize].clamped(spec.min, spec.max);
         }
 
         Genes { genes }
     }
 
     /// Reproduces the genes asexually, mutating according to the mutation factor.
-    pub fn reproduce(&self, rng: &mut WRng) -> Genes {
-        let mut new_genes = self.genes.clone();
-        for i in 0..Gene::COUNT {
-            new_genes[i] += (rng.gen::<f32>() * 2.0 - 1.0) * self.get_mutation_factor();
+    /// A gene mutates by at most the mutation factor times its mutation rate, relative to its range.
+    pub fn reproduce(&self, config: &GeneConfig, rng: &mut WRng) -> Genes {
+        let mut new_genes = self.genes;
+        for gene in Gene::iter() {
+            let spec = config.get(gene);
+            let mutation = (rng.gen::<f32>() * 2.0 - 1.0)
+                * self.get_mutation_factor()
+                * spec.mutation_rate
+                * (spec.max - spec.min);
+            new_genes[gene as usize] = spec.bound(new_genes[gene as usize] + mutation);
         }
 
         Genes { genes: new_genes }
     }
 
+    /// Feeds the gene values into a state hash.
+    pub fn hash_state(&self, hasher: &mut FnvHasher) {
+        for gene in self.genes.iter() {
+            hasher.write_f32(*gene);
+        }
+    }
+
+    /// Returns the value of any gene.
+    pub fn get(&self, gene: Gene) -> f32 {
+        self.genes[gene as usize]
+    }
+
     pub fn get_mutation_factor(&self) -> f32 {
-        self.genes[Gene::MutationFactor as usize] * MAX_MUTATION_FACTOR
+        self.genes[Gene::MutationFactor as usize]
     }
 
     pub fn get_color(&self) -> Vec3f {
@@ -70,30 +247,100 @@ impl Genes {
     }
 
     pub fn get_size(&self) -> f32 {
-        Lerp::lerp(MIN_SIZE, MAX_SIZE, self.genes[Gene::Size as usize])
+        self.genes[Gene::Size as usize]
     }
     pub fn get_speed(&self) -> f32 {
-        Lerp::lerp(MIN_SPEED, MAX_SPEED, self.genes[Gene::Speed as usize])
+        self.genes[Gene::Speed as usize]
     }
     pub fn get_eye_distance(&self) -> f32 {
-        Lerp::lerp(
-            MIN_EYE_DISTANCE,
-            MAX_EYE_DISTANCE,
-            self.genes[Gene::EyeDistance as usize],
-        )
+        self.genes[Gene::EyeDistance as usize]
     }
     pub fn get_eye_angle(&self) -> f32 {
-        Lerp::lerp(
-            MIN_EYE_ANGLE,
-            MAX_EYE_ANGLE,
-            self.genes[Gene::EyeAngle as usize],
-        )
+        self.genes[Gene::EyeAngle as usize]
     }
     pub fn get_timer_interval(&self) -> f32 {
-        Lerp::lerp(
-            MIN_TIMER_INTERVAL,
-            MAX_TIMER_INTERVAL,
-            self.genes[Gene::TimerInterval as usize],
-        )
+        self.genes[Gene::TimerInterval as usize]
+    }
+    /// Returns the age in seconds at which the agent dies of old age.
+    /// A lifespan of 0 or less means the agent does not age.
+    pub fn get_lifespan(&self) -> Option<f32> {
+        match self.genes[Gene::Lifespan as usize] {
+            lifespan if lifespan > 0.0 => Some(lifespan),
+            _ => None,
+        }
+    }
+    /// Returns how often the agent reproduces, relative to the base reproduction interval.
+    pub fn get_fertility(&self) -> f32 {
+        self.genes[Gene::Fertility as usize]
+    }
+
+    /// Returns the share of the agent's digestion devoted to the given plant species.
+    /// The shares of all species add up to 1.0.
+    pub fn get_diet(&self, species: PlantSpecies) -> f32 {
+        let total: f32 = PlantSpecies::iter()
+            .map(|species| self.genes[Gene::diet(species) as usize].max(0.0))
+            .sum();
+        if total <= 0.0 {
+            return 1.0 / PlantSpecies::COUNT as f32;
+        }
+
+        self.genes[Gene::diet(species) as usize].max(0.0) / total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_keep_genes_in_range() {
+        let mut spec = GeneSpec::new(1.0, 2.0);
+        assert_eq!(spec.bound(2.25), 2.0);
+        assert_eq!(spec.bound(0.5), 1.0);
+        spec.bounds = GeneBounds::Reflect;
+        assert_eq!(spec.bound(2.25), 1.75);
+        assert_eq!(spec.bound(0.5), 1.5);
+        assert_eq!(spec.bound(5.0), 1.0);
+    }
+
+    #[test]
+    fn mutated_genes_stay_in_range() {
+        use rand::SeedableRng;
+
+        let mut config = GeneConfig::default();
+        config.set("all", "mutation_rate", "50").unwrap();
+        config.set("size", "bounds", "reflect").unwrap();
+        let mut rng = WRng::seed_from_u64(0);
+        let mut genes = Genes::new_random(&config, &mut rng);
+        for _ in 0..100 {
+            genes = genes.reproduce(&config, &mut rng);
+            for gene in Gene::iter() {
+                let spec = config.get(gene);
+                assert!(genes.get(gene) >= spec.min && genes.get(gene) <= spec.max);
+            }
+        }
+    }
+
+    #[test]
+    fn config_rejects_unknown_genes_and_fields() {
+        let mut config = GeneConfig::default();
+        assert!(config.set("size", "max", "8").is_ok());
+        assert_eq!(config.get(Gene::Size).max, 8.0);
+        assert!(config.set("wings", "max", "8").is_err());
+        assert!(config.set("size", "color", "8").is_err());
+        assert!(config.validate().is_ok());
+        assert!(config.set("size", "min", "9").is_ok());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn lifespan_and_fertility_are_neutral_by_default() {
+        use rand::SeedableRng;
+
+        let config = GeneConfig::default();
+        let mut rng = WRng::seed_from_u64(0);
+        let genes = Genes::new_random(&config, &mut rng).reproduce(&config, &mut rng);
+        assert_eq!(genes.get_lifespan(), None);
+        assert_eq!(genes.get_fertility(), 1.0);
     }
 }
@@ -0,0 +1,234 @@
+use crate::util::{load_sections, parse_sections, parse_value, Section};
+use crate::world::agent::brain::{Command, Percept, Weights};
+use crate::world::agent::genes::Gene;
+use crate::world::Agent;
+use std::fs;
+use strum::{EnumCount, IntoEnumIterator};
+
+const FILE_HEADER: &str = "# mini-universe genomes";
+
+/// Everything an agent passes on to its offspring, its genes and brain weights,
+/// together with where it came from. Genomes can be saved to and loaded from files,
+/// so agents can be moved from one world into another.
+#[derive(Clone)]
+pub struct Genome {
+    /// Gene values, indexed by `Gene as usize`.
+    pub genes: [f32; Gene::COUNT],
+    pub weights: Weights,
+    pub generation: u32,
+    /// The seed of the world the genome was taken from.
+    pub source_seed: u64,
+    /// The simulation time at which the genome was taken.
+    pub source_time: f64,
+}
+
+impl Genome {
+    /// Takes the genome of a living agent.
+    pub fn from_agent(agent: &Agent, source_seed: u64, source_time: f64) -> Genome {
+        let mut genes = [0.0; Gene::COUNT];
+        for gene in Gene::iter() {
+            genes[gene as usize] = agent.genes.get(gene);
+        }
+
+        Genome {
+            genes,
+            weights: *agent.brain_weights(),
+            generation: agent.generation,
+            source_seed,
+            source_time,
+        }
+    }
+
+    /// Writes the genome as `key = value` lines.
+    pub fn write(&self, out: &mut String) {
+        out.push_str(&format!("generation = {}\n", self.generation));
+        out.push_str(&format!("source_seed = {}\n", self.source_seed));
+        out.push_str(&format!("source_time = {}\n", self.source_time));
+        for gene in Gene::iter() {
+            out.push_str(&format!(
+                "gene.{} = {}\n",
+                gene.as_ref(),
+                self.genes[gene as usize]
+            ));
+        }
+        for percept in Percept::iter() {
+            for command in Command::iter() {
+                out.push_str(&format!(
+                    "weight.{}.{} = {}\n",
+                    percept.as_ref(),
+                    command.as_ref(),
+                    self.weights[percept as usize * Command::COUNT + command as usize]
+                ));
+            }
+        }
+    }
+}
+
+/// A genome that is being read from a file, keeping track of which values are missing.
+pub struct PartialGenome {
+    genes: [Option<f32>; Gene::COUNT],
+    weights: [Option<f32>; Percept::COUNT * Command::COUNT],
+    generation: Option<u32>,
+    source_seed: Option<u64>,
+    source_time: Option<f64>,
+}
+
+impl PartialGenome {
+    /// Returns whether no value at all has been set.
+    pub fn is_empty(&self) -> bool {
+        self.genes
+            .iter()
+            .chain(self.weights.iter())
+            .all(|value| value.is_none())
+            && self.generation.is_none()
+            && self.source_seed.is_none()
+            && self.source_time.is_none()
+    }
+}
+
+impl Section for PartialGenome {
+    type Output = Genome;
+    const HEADER: &'static str = "[genome]";
+
+    fn new() -> PartialGenome {
+        PartialGenome {
+            genes: [None; Gene::COUNT],
+            weights: [None; Percept::COUNT * Command::COUNT],
+            generation: None,
+            source_seed: None,
+            source_time: None,
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let parts: Vec<&str> = key.split('.').collect();
+        match parts.as_slice() {
+            ["generation"] => self.generation = Some(parse_value(key, value)?),
+            ["source_seed"] => self.source_seed = Some(parse_value(key, value)?),
+            ["source_time"] => self.source_time = Some(parse_value(key, value)?),
+            ["gene", gene] => {
+                let gene = gene
+                    .parse::<Gene>()
+                    .map_err(|_| format!("unknown gene: {}", gene))?;
+                self.genes[gene as usize] = Some(parse_value(key, value)?);
+            }
+            ["weight", percept, command] => {
+                let percept = percept
+                    .parse::<Percept>()
+                    .map_err(|_| format!("unknown percept: {}", percept))?;
+                let command = command
+                    .parse::<Command>()
+                    .map_err(|_| format!("unknown command: {}", command))?;
+                self.weights[percept as usize * Command::COUNT + command as usize] =
+                    Some(parse_value(key, value)?);
+            }
+            _ => return Err(format!("unknown genome key: {}", key)),
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Genome, String> {
+        let mut genes = [0.0; Gene::COUNT];
+        for gene in Gene::iter() {
+            genes[gene as usize] = self.genes[gene as usize]
+                .ok_or_else(|| format!("genome is missing gene {}", gene.as_ref()))?;
+        }
+        let mut weights = [0.0; Percept::COUNT * Command::COUNT];
+        for (idx, weight) in self.weights.iter().enumerate() {
+            weights[idx] = weight.ok_or_else(|| "genome is missing brain weights".to_string())?;
+        }
+
+        Ok(Genome {
+            genes,
+            weights,
+            generation: self.generation.unwrap_or(1),
+            source_seed: self.source_seed.unwrap_or(0),
+            source_time: self.source_time.unwrap_or(0.0),
+        })
+    }
+}
+
+/// Converts genomes to the genome file format.
+pub fn genomes_to_string(genomes: &[Genome]) -> String {
+    let mut out = String::new();
+    out.push_str(FILE_HEADER);
+    out.push('\n');
+    for genome in genomes {
+        out.push('\n');
+        out.push_str(PartialGenome::HEADER);
+        out.push('\n');
+        genome.write(&mut out);
+    }
+    out
+}
+
+/// Parses genomes from the genome file format.
+/// Every genome starts with a `[genome]` line, followed by `key = value` lines.
+pub fn genomes_from_str(contents: &str) -> Result<Vec<Genome>, String> {
+    parse_sections::<PartialGenome>(contents)
+}
+
+/// Saves genomes to a file.
+pub fn save_genomes(path: &str, genomes: &[Genome]) -> Result<(), String> {
+    fs::write(path, genomes_to_string(genomes)).map_err(|err| format!("{}: {}", path, err))
+}
+
+/// Loads genomes from a file. The file must contain at least one genome.
+pub fn load_genomes(path: &str) -> Result<Vec<Genome>, String> {
+    load_sections::<PartialGenome>(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::WRng;
+    use crate::world::{Origin, Params};
+    use rand::SeedableRng;
+
+    #[test]
+    fn genomes_survive_a_round_trip() {
+        let params = Params::default();
+        let mut rng = WRng::seed_from_u64(3);
+        let genomes: Vec<Genome> = (0..3)
+            .map(|_| {
+                let agent = Agent::new_random(&params, Origin::Founder, 0, &mut rng);
+                Genome::from_agent(&agent, 3, 12.5)
+            })
+            .collect();
+
+        let loaded = genomes_from_str(&genomes_to_string(&genomes)).unwrap();
+
+        assert_eq!(loaded.len(), 3);
+        for (genome, loaded) in genomes.iter().zip(loaded.iter()) {
+            assert_eq!(genome.genes, loaded.genes);
+            assert_eq!(genome.weights, loaded.weights);
+            assert_eq!(loaded.generation, 1);
+            assert_eq!(loaded.source_seed, 3);
+            assert_eq!(loaded.source_time, 12.5);
+        }
+    }
+
+    #[test]
+    fn incomplete_genomes_are_rejected() {
+        let params = Params::default();
+        let agent = Agent::new_random(&params, Origin::Founder, 0, &mut WRng::seed_from_u64(3));
+        let contents = genomes_to_string(&[Genome::from_agent(&agent, 3, 0.0)]);
+
+        let without_size: String = contents
+            .lines()
+            .filter(|line| !line.starts_with("gene.size"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        // The genome is only found incomplete at the end of the file, but the error still
+        // points to its header.
+        let header_line = contents
+            .lines()
+            .position(|line| line == PartialGenome::HEADER)
+            .unwrap();
+        let err = genomes_from_str(&without_size).err().unwrap();
+        assert!(err.starts_with(&format!("line {}: ", header_line + 1)));
+        assert!(genomes_from_str(&contents.replace("gene.size", "gene.wings")).is_err());
+        assert!(genomes_from_str("generation = 3").is_err());
+    }
+}
@@ -1,5 +1,9 @@
 pub mod agent;
-mod brain;
-mod genes;
+pub mod brain;
+pub mod genes;
+pub mod genome;
 
-pub use agent::Agent;
+pub use agent::{Agent, Origin};
+pub use brain::{Command, Percept};
+pub use genes::{Gene, GeneBounds, GeneConfig};
+pub use genome::Genome;
@@ -0,0 +1,73 @@
+//! Regression tests that guard the reproducibility of seeded simulations.
+//! If a change is meant to alter the simulation, update the golden hashes below.
+
+use crate::world::{Params, TimeController, World};
+
+fn run_ticks(params: &Params, seed: u64, ticks: u32) -> World {
+    let mut world = World::new_seeded(params, seed);
+    for _ in 0..ticks {
+        world.tick(params, params.tick_interval);
+    }
+    world
+}
+
+#[test]
+fn golden_hashes() {
+    let params = Params::default();
+    let golden: [(u64, u64); 3] = [
+        (1, 12673324608761841145),
+        (42, 10468307985769503491),
+        (1234, 15178575005639655854),
+    ];
+    for (seed, expected_hash) in golden.iter() {
+        let world = run_ticks(&params, *seed, 5000);
+        assert_eq!(world.state_hash(), *expected_hash, "seed {}", seed);
+    }
+}
+
+#[test]
+fn golden_hash_without_evolution() {
+    let mut params = Params::default();
+    params.evolution = false;
+    params.agent_count = 20;
+    let world = run_ticks(&params, 7, 2500);
+    assert_eq!(world.state_hash(), 15523008001796728563);
+}
+
+#[test]
+fn same_seed_same_result() {
+    let params = Params::default();
+    let world_1 = run_ticks(&params, 99, 1000);
+    let world_2 = run_ticks(&params, 99, 1000);
+    assert_eq!(world_1.state_hash(), world_2.state_hash());
+}
+
+#[test]
+fn independent_of_thread_count() {
+    let mut params = Params::default();
+    params.agent_count = 50;
+    let hash_with_threads = |threads| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| run_ticks(&params, 3, 2000).state_hash())
+    };
+    assert_eq!(hash_with_threads(1), hash_with_threads(4));
+}
+
+#[test]
+fn goto_backwards_matches_running_forward() {
+    let params = Params::default();
+    let mut time_controller = TimeController::new();
+
+    let mut world = World::new_seeded(&params, 5);
+    time_controller.goto(&params, &mut world, 120.0);
+    time_controller.goto(&params, &mut world, 60.0);
+
+    let mut forward_world = World::new_seeded(&params, 5);
+    time_controller.goto(&params, &mut forward_world, 60.0);
+
+    assert_eq!(world.time, forward_world.time);
+    assert_eq!(world.state_hash(), forward_world.state_hash());
+}
@@ -0,0 +1,37 @@
+use crate::util::Vec2f;
+use crate::world::{Genome, PlantSpecies};
+
+/// A change to the world made by hand, for example with the editor of the window.
+/// Edits are kept in a log with the time they were made, so time travel can replay them.
+#[derive(Clone)]
+pub enum Edit {
+    /// Fills the cells within the radius with a plant species.
+    Paint {
+        species: PlantSpecies,
+        center: Vec2f,
+        radius: f32,
+    },
+    /// Removes all plants within the radius.
+    Erase {
+        center: Vec2f,
+        radius: f32,
+    },
+    /// Places or removes walls within the radius.
+    SetWalls {
+        center: Vec2f,
+        radius: f32,
+        wall: bool,
+    },
+    /// Adds an agent created from a genome, or at random if there is none.
+    Spawn {
+        genome: Option<Genome>,
+        pos: Vec2f,
+    },
+    Remove {
+        id: u64,
+    },
+    Move {
+        id: u64,
+        pos: Vec2f,
+    },
+}
@@ -0,0 +1,284 @@
+use crate::util::parse_value;
+use crate::world::{Params, World};
+use std::fmt;
+use std::time::Instant;
+
+/// Conditions that end a run. A run ends as soon as any of the enabled conditions is met.
+#[derive(Clone)]
+pub struct EndConditions {
+    /// End when all agents are dead, even if immigrants would arrive afterwards.
+    pub extinction: bool,
+    /// End when an agent reaches this generation.
+    pub generation: Option<u32>,
+    /// End when the number of agents hasn't changed for this many simulated seconds.
+    pub stable_time: Option<f64>,
+    /// End after this many simulated seconds.
+    pub time: Option<f64>,
+    /// End after this many real seconds.
+    pub wall_time: Option<f64>,
+}
+
+impl EndConditions {
+    /// Creates EndConditions with every condition disabled, so a run never ends.
+    pub fn none() -> EndConditions {
+        EndConditions {
+            extinction: false,
+            generation: None,
+            stable_time: None,
+            time: None,
+            wall_time: None,
+        }
+    }
+
+    /// Returns whether any condition is enabled.
+    pub fn any(&self) -> bool {
+        self.extinction
+            || self.generation.is_some()
+            || self.stable_time.is_some()
+            || self.time.is_some()
+            || self.wall_time.is_some()
+    }
+
+    /// Sets a single condition by name, parsing the value from a string.
+    /// Conditions other than `extinction` are disabled with `none`.
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        fn parse_optional<T: std::str::FromStr>(
+            key: &str,
+            value: &str,
+        ) -> Result<Option<T>, String> {
+            match value {
+                "none" => Ok(None),
+                _ => parse_value(key, value).map(Some),
+            }
+        }
+
+        let key = format!("end.{}", field);
+
+        match field {
+            "extinction" => self.extinction = parse_value(&key, value)?,
+            "generation" => self.generation = parse_optional(&key, value)?,
+            "stable_time" => self.stable_time = parse_optional(&key, value)?,
+            "time" => self.time = parse_optional(&key, value)?,
+            "wall_time" => self.wall_time = parse_optional(&key, value)?,
+            _ => return Err(format!("unknown end condition: {}", field)),
+        }
+
+        Ok(())
+    }
+}
+
+/// Why a run ended.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EndReason {
+    Extinction,
+    /// An agent of the lineage reached the generation.
+    Generation {
+        lineage: u32,
+        generation: u32,
+    },
+    /// The number of agents stayed the same.
+    Stable {
+        population: usize,
+    },
+    Time,
+    WallTime,
+}
+
+impl fmt::Display for EndReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EndReason::Extinction => write!(f, "all agents died"),
+            EndReason::Generation {
+                lineage,
+                generation,
+            } => write!(f, "lineage {} reached generation {}", lineage, generation),
+            EndReason::Stable { population } => {
+                write!(f, "population stable at {} agents", population)
+            }
+            EndReason::Time => write!(f, "time limit reached"),
+            EndReason::WallTime => write!(f, "wall-clock limit reached"),
+        }
+    }
+}
+
+/// Watches a running world and tells when one of the end conditions is met.
+/// Must be created anew whenever the world is replaced.
+pub struct EndMonitor {
+    start: Instant,
+    population: usize,
+    /// The simulation time at which the number of agents last changed.
+    population_changed_at: f64,
+    /// The number of extinctions before the monitor was created, which do not count.
+    extinctions: u64,
+}
+
+impl EndMonitor {
+    pub fn new(world: &World) -> EndMonitor {
+        EndMonitor {
+            start: Instant::now(),
+            population: world.agents.len(),
+            population_changed_at: world.time,
+            extinctions: world.statistics.extinctions,
+        }
+    }
+
+    /// Checks the end conditions against the current state of the world.
+    /// Should be called after every tick, or the stable population condition may be missed.
+    pub fn check(&mut self, world: &World, conditions: &EndConditions) -> Option<EndReason> {
+        if world.agents.len() != self.population {
+            self.population = world.agents.len();
+            self.population_changed_at = world.time;
+        }
+
+        let extinct = world.agents.is_empty() || world.statistics.extinctions > self.extinctions;
+        if conditions.extinction && extinct {
+            return Some(EndReason::Extinction);
+        }
+        if let Some(generation) = conditions.generation {
+            if let Some(agent) = world
+                .agents
+                .iter()
+                .find(|agent| agent.generation >= generation)
+            {
+                return Some(EndReason::Generation {
+                    lineage: agent.lineage,
+                    generation: agent.generation,
+                });
+            }
+        }
+        if let Some(stable_time) = conditions.stable_time {
+            if world.time - self.population_changed_at >= stable_time {
+                return Some(EndReason::Stable {
+                    population: self.population,
+                });
+            }
+        }
+        if let Some(time) = conditions.time {
+            if world.time >= time {
+                return Some(EndReason::Time);
+            }
+        }
+        if let Some(wall_time) = conditions.wall_time {
+            if self.start.elapsed().as_secs_f64() >= wall_time {
+                return Some(EndReason::WallTime);
+            }
+        }
+
+        None
+    }
+}
+
+/// Runs a world until one of the end conditions is met, checking after every tick.
+/// `observe` sees the world before every check, and stops the run when it fails.
+/// Never returns if no condition is enabled.
+pub fn run_until_end<E>(
+    world: &mut World,
+    params: &Params,
+    mut observe: impl FnMut(&World) -> Result<(), E>,
+) -> Result<EndReason, E> {
+    let mut monitor = EndMonitor::new(world);
+    loop {
+        observe(world)?;
+        if let Some(reason) = monitor.check(world, &params.end) {
+            return Ok(reason);
+        }
+        world.tick(params, params.tick_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Immigration;
+
+    fn run(world: &mut World, params: &Params) -> EndReason {
+        run_until_end(world, params, |_| Ok::<(), ()>(())).unwrap()
+    }
+
+    #[test]
+    fn extinction_ends_a_run_despite_immigration() {
+        let mut params = Params::default();
+        params.end.extinction = true;
+        params.end.time = Some(100.0);
+        let mut world = World::new_seeded(&params, 1);
+        for agent in world.agents.iter_mut() {
+            agent.energy = -1.0;
+        }
+
+        assert_eq!(run(&mut world, &params), EndReason::Extinction);
+        assert_eq!(world.statistics.extinctions, 1);
+        // The floor immigration policy refilled the world in the same tick.
+        assert_eq!(world.agents.len(), 5);
+    }
+
+    #[test]
+    fn only_new_extinctions_end_a_run() {
+        let mut params = Params::default();
+        params.end.extinction = true;
+        let mut world = World::new_seeded(&params, 1);
+        // An extinction that happened before, for example before travelling back in time.
+        world.statistics.extinctions = 1;
+
+        let mut monitor = EndMonitor::new(&world);
+        assert_eq!(monitor.check(&world, &params.end), None);
+        for agent in world.agents.iter_mut() {
+            agent.energy = -1.0;
+        }
+        world.tick(&params, params.tick_interval);
+        assert_eq!(world.agents.len(), 5);
+        assert_eq!(
+            monitor.check(&world, &params.end),
+            Some(EndReason::Extinction)
+        );
+    }
+
+    #[test]
+    fn generation_and_time_end_a_run() {
+        let mut params = Params::default();
+        params.end.generation = Some(3);
+        let mut world = World::new_seeded(&params, 1);
+        world.agents[2].generation = 3;
+        let lineage = world.agents[2].lineage;
+        assert_eq!(
+            run(&mut world, &params),
+            EndReason::Generation {
+                lineage,
+                generation: 3
+            }
+        );
+
+        params.end.generation = None;
+        params.end.time = Some(2.0);
+        let mut world = World::new_seeded(&params, 1);
+        assert_eq!(run(&mut world, &params), EndReason::Time);
+        assert!(world.time >= 2.0 && world.time < 2.0 + params.tick_interval as f64);
+    }
+
+    #[test]
+    fn stable_population_ends_a_run() {
+        let mut params = Params::default();
+        params.evolution = false;
+        params.immigration = Immigration::None;
+        params.end.stable_time = Some(10.0);
+        let mut world = World::new_seeded(&params, 1);
+
+        assert_eq!(
+            run(&mut world, &params),
+            EndReason::Stable { population: 5 }
+        );
+        assert!(world.time >= 10.0 && world.time < 10.0 + params.tick_interval as f64);
+    }
+
+    #[test]
+    fn conditions_are_parsed() {
+        let mut conditions = EndConditions::none();
+        assert!(!conditions.any());
+        conditions.set("generation", "20").unwrap();
+        assert_eq!(conditions.generation, Some(20));
+        assert!(conditions.any());
+        conditions.set("generation", "none").unwrap();
+        assert!(!conditions.any());
+        assert!(conditions.set("extinction", "none").is_err());
+        assert!(conditions.set("heat_death", "1").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::util::{load_sections, parse_sections, parse_value, Section, Vec2f};
+use crate::world::agent::genome::PartialGenome;
+use crate::world::{Agent, Genome};
+use std::fs;
+
+const FILE_HEADER: &str = "# mini-universe founders";
+
+/// A group of identical agents that a world starts with.
+#[derive(Clone)]
+pub struct Founder {
+    /// The genome of the agents, or `None` for random genomes.
+    pub genome: Option<Genome>,
+    /// The position of the agents, or `None` for random positions.
+    pub pos: Option<Vec2f>,
+    /// The angle of the agents in radians, or `None` for random angles.
+    pub angle: Option<f32>,
+    /// The number of agents.
+    pub count: u32,
+}
+
+impl Founder {
+    /// Creates a founder that places a single copy of a living agent where it is.
+    pub fn from_agent(agent: &Agent, source_seed: u64, source_time: f64) -> Founder {
+        Founder {
+            genome: Some(Genome::from_agent(agent, source_seed, source_time)),
+            pos: Some(agent.pos),
+            angle: Some(agent.angle),
+            count: 1,
+        }
+    }
+
+    /// Writes the founder as `key = value` lines.
+    fn write(&self, out: &mut String) {
+        out.push_str(&format!("count = {}\n", self.count));
+        if let Some(pos) = self.pos {
+            out.push_str(&format!("x = {}\n", pos.x));
+            out.push_str(&format!("y = {}\n", pos.y));
+        }
+        if let Some(angle) = self.angle {
+            out.push_str(&format!("angle = {}\n", angle));
+        }
+        if let Some(genome) = &self.genome {
+            genome.write(out);
+        }
+    }
+}
+
+/// A founder that is being read from a file.
+struct PartialFounder {
+    genome: PartialGenome,
+    x: Option<f32>,
+    y: Option<f32>,
+    angle: Option<f32>,
+    count: u32,
+}
+
+impl Section for PartialFounder {
+    type Output = Founder;
+    const HEADER: &'static str = "[founder]";
+
+    fn new() -> PartialFounder {
+        PartialFounder {
+            genome: PartialGenome::new(),
+            x: None,
+            y: None,
+            angle: None,
+            count: 1,
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "x" => self.x = Some(parse_value(key, value)?),
+            "y" => self.y = Some(parse_value(key, value)?),
+            "angle" => self.angle = Some(parse_value(key, value)?),
+            "count" => self.count = parse_value(key, value)?,
+            _ => self.genome.set(key, value)?,
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Founder, String> {
+        let pos = match (self.x, self.y) {
+            (Some(x), Some(y)) => Some(Vec2f::new(x, y)),
+            (None, None) => None,
+            _ => return Err("founder needs both x and y, or neither".to_string()),
+        };
+        let genome = if self.genome.is_empty() {
+            None
+        } else {
+            Some(self.genome.finish()?)
+        };
+
+        Ok(Founder {
+            genome,
+            pos,
+            angle: self.angle,
+            count: self.count,
+        })
+    }
+}
+
+/// Converts founders to the founder file format.
+pub fn founders_to_string(founders: &[Founder]) -> String {
+    let mut out = String::new();
+    out.push_str(FILE_HEADER);
+    out.push('\n');
+    for founder in founders {
+        out.push('\n');
+        out.push_str(PartialFounder::HEADER);
+        out.push('\n');
+        founder.write(&mut out);
+    }
+    out
+}
+
+/// Parses founders from the founder file format.
+/// Every founder starts with a `[founder]` line, followed by `key = value` lines
+/// with its count, position, angle and the keys of a genome file.
+/// Values that are left out are random.
+pub fn founders_from_str(contents: &str) -> Result<Vec<Founder>, String> {
+    parse_sections::<PartialFounder>(contents)
+}
+
+/// Saves founders to a file.
+pub fn save_founders(path: &str, founders: &[Founder]) -> Result<(), String> {
+    fs::write(path, founders_to_string(founders)).map_err(|err| format!("{}: {}", path, err))
+}
+
+/// Loads founders from a file. The file must contain at least one founder.
+pub fn load_founders(path: &str) -> Result<Vec<Founder>, String> {
+    load_sections::<PartialFounder>(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{Params, World};
+
+    #[test]
+    fn founders_survive_a_round_trip() {
+        let world = World::new_seeded(&Params::default(), 4);
+        let founders = world.export_founders();
+
+        let loaded = founders_from_str(&founders_to_string(&founders)).unwrap();
+
+        assert_eq!(loaded.len(), world.agents.len());
+        for (agent, founder) in world.agents.iter().zip(loaded.iter()) {
+            assert_eq!(founder.pos, Some(agent.pos));
+            assert_eq!(founder.angle, Some(agent.angle));
+            assert_eq!(founder.count, 1);
+            assert_eq!(
+                founder.genome.as_ref().unwrap().weights,
+                *agent.brain_weights()
+            );
+        }
+    }
+
+    #[test]
+    fn left_out_values_are_random() {
+        let founders =
+            founders_from_str("[founder]\ncount = 3\n\n[founder]\nx = 1\ny = 2\n").unwrap();
+
+        assert_eq!(founders.len(), 2);
+        assert_eq!(founders[0].count, 3);
+        assert!(founders[0].genome.is_none() && founders[0].pos.is_none());
+        assert_eq!(founders[1].count, 1);
+        assert_eq!(founders[1].pos, Some(Vec2f::new(1.0, 2.0)));
+        assert!(founders[1].angle.is_none());
+        assert!(founders_from_str("[founder]\nx = 1\n").is_err());
+        // A genome needs all of its genes and weights, even if only its generation is given.
+        assert!(founders_from_str("[founder]\ngeneration = 3\n").is_err());
+        assert!(founders_from_str("[founder]\nsource_seed = 7\n").is_err());
+    }
+}
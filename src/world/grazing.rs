@@ -0,0 +1,82 @@
+use crate::util::{Size2i, Vec2i};
+
+/// Where agents have eaten, per plant grid cell. Used to show grazing pressure and territories.
+/// It doesn't affect the simulation, so it isn't hashed.
+pub struct GrazingMap {
+    pub size: Size2i,
+    /// The energy agents gained by eating in every cell since the start, row by row.
+    /// Poison subtracts from it.
+    energy_eaten: Vec<f32>,
+    /// The last time an agent ate in every cell, or `None` if none has.
+    last_grazed: Vec<Option<f64>>,
+    /// The lineage of the last agent that ate in every cell.
+    last_lineage: Vec<u32>,
+}
+
+impl GrazingMap {
+    pub fn new(size: Size2i) -> GrazingMap {
+        let cells = size.w as usize * size.h as usize;
+        GrazingMap {
+            size,
+            energy_eaten: vec![0.0; cells],
+            last_grazed: vec![None; cells],
+            last_lineage: vec![0; cells],
+        }
+    }
+
+    fn index(&self, cell: Vec2i) -> usize {
+        cell.y as usize * self.size.w as usize + cell.x as usize
+    }
+
+    /// Records that an agent of a lineage ate in a cell at the given time.
+    pub fn record(&mut self, cell: Vec2i, time: f64, lineage: u32, energy: f32) {
+        let idx = self.index(cell);
+        self.energy_eaten[idx] += energy;
+        self.last_grazed[idx] = Some(time);
+        self.last_lineage[idx] = lineage;
+    }
+
+    /// The energy agents gained by eating in all cells, row by row.
+    pub fn energy_eaten(&self) -> &[f32] {
+        &self.energy_eaten
+    }
+
+    pub fn last_grazed(&self, cell: Vec2i) -> Option<f64> {
+        self.last_grazed[self.index(cell)]
+    }
+
+    /// The lineage of the last agent that ate in a cell, if any agent has.
+    pub fn last_lineage(&self, cell: Vec2i) -> Option<u32> {
+        let idx = self.index(cell);
+        self.last_grazed[idx].map(|_| self.last_lineage[idx])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{Params, World};
+
+    #[test]
+    fn eating_is_recorded() {
+        let mut grazing = GrazingMap::new(Size2i::new(4, 3));
+        let cell = Vec2i::new(3, 2);
+        grazing.record(cell, 1.5, 7, 0.25);
+        grazing.record(cell, 2.5, 8, -0.1);
+        assert!((grazing.energy_eaten()[11] - 0.15).abs() < 1e-6);
+        assert_eq!(grazing.last_grazed(cell), Some(2.5));
+        assert_eq!(grazing.last_lineage(cell), Some(8));
+        assert_eq!(grazing.last_lineage(Vec2i::new(2, 2)), None);
+
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        while world.time < 60.0 {
+            world.tick(&params, params.tick_interval);
+        }
+        assert!(world
+            .grazing
+            .energy_eaten()
+            .iter()
+            .any(|energy| *energy != 0.0));
+    }
+}
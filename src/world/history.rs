@@ -0,0 +1,115 @@
+use crate::world::{Agent, Gene, PlantGrid, PlantSpecies};
+use std::collections::VecDeque;
+use strum::{EnumCount, IntoEnumIterator};
+
+/// Simulated seconds between samples.
+pub const SAMPLE_INTERVAL: f64 = 1.0;
+/// The number of samples that are kept. Older samples are dropped.
+pub const HISTORY_LENGTH: usize = 600;
+
+/// The state of a world at one point in time, reduced to a few numbers.
+/// The means are NaN while there are no agents.
+#[derive(Clone)]
+pub struct Sample {
+    pub time: f64,
+    pub population: usize,
+    pub mean_energy: f32,
+    /// The density of all plant species together, as a fraction of a full cell.
+    pub plant_density: f32,
+    /// The mean value of every gene, indexed by `Gene as usize`.
+    pub mean_genes: [f32; Gene::COUNT],
+}
+
+impl Sample {
+    pub fn new(time: f64, agents: &[Agent], plant_grid: &PlantGrid) -> Sample {
+        let count = agents.len() as f32;
+        let mut mean_genes = [0.0; Gene::COUNT];
+        for gene in Gene::iter() {
+            mean_genes[gene as usize] = agents
+                .iter()
+                .map(|agent| agent.genes.get(gene))
+                .sum::<f32>()
+                / count;
+        }
+        let total_density: u64 = PlantSpecies::iter()
+            .map(|species| plant_grid.total_density(species))
+            .sum();
+        let cells = plant_grid.size.w as f32 * plant_grid.size.h as f32;
+
+        Sample {
+            time,
+            population: agents.len(),
+            mean_energy: agents.iter().map(|agent| agent.energy).sum::<f32>() / count,
+            plant_density: total_density as f32 / (cells * u8::MAX as f32),
+            mean_genes,
+        }
+    }
+}
+
+/// The recent history of a world, sampled at a fixed simulated interval and kept in a ring
+/// buffer. It doesn't affect the simulation, so it isn't hashed.
+pub struct History {
+    samples: VecDeque<Sample>,
+    next_time: f64,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            samples: VecDeque::with_capacity(HISTORY_LENGTH),
+            next_time: 0.0,
+        }
+    }
+
+    /// The samples, oldest first.
+    pub fn samples(&self) -> &VecDeque<Sample> {
+        &self.samples
+    }
+
+    /// Takes a sample if it is time for one, dropping the oldest sample if the buffer is full.
+    pub fn update(&mut self, time: f64, agents: &[Agent], plant_grid: &PlantGrid) {
+        if time < self.next_time {
+            return;
+        }
+        while self.next_time <= time {
+            self.next_time += SAMPLE_INTERVAL;
+        }
+        if self.samples.len() == HISTORY_LENGTH {
+            self.samples.pop_front();
+        }
+        self.samples
+            .push_back(Sample::new(time, agents, plant_grid));
+    }
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::{Params, World};
+
+    #[test]
+    fn samples_are_taken_at_the_interval() {
+        let params = Params::default();
+        let mut world = World::new_seeded(&params, 1);
+        while world.time < 10.5 {
+            world.tick(&params, params.tick_interval);
+        }
+        let samples = world.history.samples();
+        assert_eq!(samples.len(), 11);
+        assert_eq!(samples[0].time, 0.0);
+        assert_eq!(samples[0].population, params.agent_count as usize);
+        assert!(samples.iter().all(|sample| sample.plant_density > 0.0));
+
+        world.agents.clear();
+        world.time = 20.0;
+        world.tick(&params, params.tick_interval);
+        let last = world.history.samples().back().unwrap();
+        assert_eq!(last.population, 0);
+        assert!(last.mean_energy.is_nan());
+    }
+}
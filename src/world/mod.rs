@@ -1,11 +1,27 @@
 pub mod agent;
+#[cfg(test)]
+mod determinism;
+pub mod edit;
+pub mod end_conditions;
+pub mod founders;
+pub mod grazing;
+pub mod history;
 pub mod params;
 pub mod plant_grid;
+pub mod plant_species;
+pub mod statistics;
 pub mod time_controller;
 pub mod world;
 
-pub use agent::Agent;
-pub use params::Params;
+pub use agent::{Agent, Gene, GeneConfig, Genome, Origin};
+pub use edit::Edit;
+pub use end_conditions::{run_until_end, EndConditions, EndMonitor, EndReason};
+pub use founders::Founder;
+pub use grazing::GrazingMap;
+pub use history::History;
+pub use params::{Immigration, Params, Topology};
 pub use plant_grid::PlantGrid;
+pub use plant_species::PlantSpecies;
+pub use statistics::Statistics;
 pub use time_controller::TimeController;
 pub use world::World;
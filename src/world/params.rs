@@ -1,12 +1,56 @@
-use crate::util::Size2i;
+use crate::util::{parse_positive_value, parse_value, read_settings_file, split_setting, Size2i};
+use crate::world::agent::genome::load_genomes;
+use crate::world::agent::{GeneConfig, Genome};
+use crate::world::end_conditions::EndConditions;
+use crate::world::founders::{load_founders, Founder};
+use strum_macros::{AsRefStr, EnumString};
+
+/// The shape of the world at its borders.
+#[derive(Copy, Clone, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Topology {
+    /// Opposite borders are connected, for agents as well as plants.
+    Torus,
+    /// The world is surrounded by walls. Nothing grows or can be seen beyond them.
+    Walled,
+}
+
+/// Where new random agents come from once the world is running.
+#[derive(Copy, Clone, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Immigration {
+    /// No agents are added, so the population may die out.
+    None,
+    /// Agents arrive at a fixed rate, see `Params::immigration_rate`.
+    Rate,
+    /// Agents are added whenever the population falls below `Params::agent_count`.
+    Floor,
+}
 
 /// World parameters that might be modified by an end user.
+#[derive(Clone)]
 pub struct Params {
     pub seed: Option<u64>,
     pub plant_grid_size: Size2i,
+    pub topology: Topology,
     pub tick_interval: f32,
+    /// The initial number of agents.
     pub agent_count: u32,
     pub evolution: bool,
+    pub immigration: Immigration,
+    /// Immigrants per second, if `immigration` is `Rate`.
+    pub immigration_rate: f32,
+    /// The maximum number of agents. Newborns and immigrants beyond it are not added.
+    pub carrying_capacity: Option<u32>,
+    pub genes: GeneConfig,
+    /// Imported genomes that the initial agents are created from, instead of random ones.
+    /// If there are fewer genomes than agents, the genomes are used repeatedly.
+    pub genomes: Vec<Genome>,
+    /// The initial agents, instead of `agent_count` agents created from `genomes` or at random.
+    /// `agent_count` is still used by `Immigration::Floor`.
+    pub founders: Vec<Founder>,
+    /// When a run ends. The GUI pauses, headless runs stop.
+    pub end: EndConditions,
 }
 
 impl Params {
@@ -15,9 +59,143 @@ impl Params {
         Params {
             seed: None,
             plant_grid_size: Size2i::new(200, 200),
+            topology: Topology::Torus,
             tick_interval: 0.04,
             agent_count: 5,
             evolution: true,
+            immigration: Immigration::Floor,
+            immigration_rate: 0.01,
+            carrying_capacity: None,
+            genes: GeneConfig::default(),
+            genomes: Vec::new(),
+            founders: Vec::new(),
+            end: EndConditions::none(),
+        }
+    }
+
+    /// Sets a single parameter by name, parsing the value from a string.
+    /// Gene specifications are set with keys like `gene.size.max` or `gene.all.mutation_rate`,
+    /// and end conditions with keys like `end.generation`.
+    /// `config`, `genomes` and `founders` load a parameter, genome or founder file.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "seed" => self.seed = Some(parse_value(key, value)?),
+            "plant_grid_width" => self.plant_grid_size.w = parse_positive_value(key, value)?,
+            "plant_grid_height" => self.plant_grid_size.h = parse_positive_value(key, value)?,
+            "topology" => self.topology = parse_value(key, value)?,
+            "tick_interval" => self.tick_interval = parse_positive_value(key, value)?,
+            "agent_count" => self.agent_count = parse_value(key, value)?,
+            "evolution" => self.evolution = parse_value(key, value)?,
+            "immigration" => self.immigration = parse_value(key, value)?,
+            "immigration_rate" => self.immigration_rate = parse_value(key, value)?,
+            "carrying_capacity" => {
+                self.carrying_capacity = match value {
+                    "none" => None,
+                    _ => Some(parse_value(key, value)?),
+                }
+            }
+            "config" => self.apply_file(value)?,
+            "genomes" => self.genomes = load_genomes(value)?,
+            "founders" => self.founders = load_founders(value)?,
+            _ if key.starts_with("end.") => self.end.set(&key["end.".len()..], value)?,
+            _ if key.starts_with("gene.") => {
+                let mut parts = key.splitn(3, '.').skip(1);
+                match (parts.next(), parts.next()) {
+                    (Some(gene), Some(field)) => self.genes.set(gene, field, value)?,
+                    _ => return Err(format!("expected gene.<gene>.<field>, got: {}", key)),
+                }
+            }
+            _ => return Err(format!("unknown parameter: {}", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Loads a parameter file with one `key = value` pair per line,
+    /// and checks that the result is valid.
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn load(&mut self, path: &str) -> Result<(), String> {
+        self.apply_file(path)?;
+        self.genes.validate()
+    }
+
+    fn apply_file(&mut self, path: &str) -> Result<(), String> {
+        read_settings_file(path, |key, value| self.set(key, value))
+    }
+
+    /// Applies a list of `key=value` overrides, and checks that the result is valid.
+    pub fn apply_overrides(&mut self, overrides: &[String]) -> Result<(), String> {
+        for arg in overrides {
+            let (key, value) = split_setting(arg)?;
+            self.set(key, value)?;
         }
+        self.genes.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_and_intervals_must_be_positive() {
+        let mut params = Params::default();
+        assert!(params.set("plant_grid_width", "0").is_err());
+        assert!(params.set("plant_grid_height", "-3").is_err());
+        assert!(params.set("tick_interval", "0").is_err());
+        assert!(params.set("tick_interval", "-0.1").is_err());
+        assert_eq!(params.plant_grid_size, Params::default().plant_grid_size);
+
+        params.set("plant_grid_width", "50").unwrap();
+        params.set("tick_interval", "0.5").unwrap();
+        assert_eq!(params.plant_grid_size.w, 50);
+        assert_eq!(params.tick_interval, 0.5);
+    }
+
+    /// Sets a single parameter on the default parameters and checks the gene ranges.
+    fn set_gene(key: &str, value: &str) -> Result<(), String> {
+        let mut params = Params::default();
+        params.set(key, value)?;
+        params.genes.validate()
+    }
+
+    #[test]
+    fn gene_ranges_must_be_valid() {
+        assert!(set_gene("gene.size.min", "0").is_err());
+        assert!(set_gene("gene.timer_interval.min", "0").is_err());
+        assert!(set_gene("gene.fertility.min", "0").is_err());
+        assert!(set_gene("gene.fertility.min", "-1").is_err());
+        assert!(set_gene("gene.lifespan.min", "-1").is_err());
+        assert!(set_gene("gene.diet_grass.min", "-0.5").is_err());
+        assert!(set_gene("gene.diet_poison.min", "-0.5").is_err());
+        assert_eq!(
+            set_gene("gene.speed.min", "11"),
+            Err("gene speed has min above max".to_string())
+        );
+        assert_eq!(
+            set_gene("gene.size.min", "0"),
+            Err("gene size must be positive".to_string())
+        );
+
+        assert!(set_gene("gene.fertility.min", "0.5").is_ok());
+        assert!(set_gene("gene.lifespan.max", "300").is_ok());
+        assert!(set_gene("gene.diet_berries.min", "0").is_ok());
+        assert!(set_gene("gene.speed.min", "0").is_ok());
+    }
+
+    #[test]
+    fn files_report_the_line_of_an_error() {
+        let path = std::env::temp_dir().join("mini-universe-params-test.txt");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "# A comment\n\nagent_count = 8\ntick_interval = 0\n").unwrap();
+
+        let mut params = Params::default();
+        let err = params.load(path).err().unwrap();
+        assert_eq!(
+            err,
+            format!("{}: line 4: tick_interval must be positive", path)
+        );
+        assert_eq!(params.agent_count, 8);
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -1,65 +1,223 @@
-use crate::util::{Size2i, Vec2i, WRng};
+use crate::util::{FnvHasher, Size2i, Vec2f, Vec2i, WRng};
+use crate::world::params::Topology;
+use crate::world::plant_species::PlantSpecies;
 use rand::Rng;
+use std::hash::Hasher;
+use strum::{EnumCount, IntoEnumIterator};
+use vek::ops::Clamp;
 
-const GENERATE_DENSITY_THRESHOLD: f32 = 0.995;
 const GENERATE_REGENERATE: u32 = 100;
 const REGENERATE_INTERVAL: f32 = 10.0;
-const REGENERATE_NEIGHBOR_THRESHOLD: f32 = 100.0;
-const REGENERATE_INCREMENT_MAX: f32 = 3.0;
-
-const TARGET_DENSITY_PER_CELL: f32 = 8.0;
 
 #[derive(Clone)]
 pub struct PlantGrid {
-    pub densities: Vec<u8>,
+    /// One density channel per plant species, indexed by `PlantSpecies as usize`.
+    densities: Vec<Vec<u8>>,
+    /// Running total density per species, kept up to date by `set_density`.
+    total_densities: Vec<u64>,
+    /// Running total density per species and row, kept up to date by `set_density`.
+    row_densities: Vec<Vec<u32>>,
+    /// Whether every cell is a wall, row by row. Nothing grows in walls and agents can't enter
+    /// them. Walls are placed by hand.
+    walls: Vec<bool>,
     pub size: Size2i,
+    pub topology: Topology,
     time_since_regenerate: f32,
 }
 
 impl PlantGrid {
-    pub fn new(size: Size2i) -> PlantGrid {
+    pub fn new(size: Size2i, topology: Topology) -> PlantGrid {
         PlantGrid {
-            densities: vec![0u8; size.w as usize * size.h as usize],
+            densities: vec![vec![0u8; size.w as usize * size.h as usize]; PlantSpecies::COUNT],
+            total_densities: vec![0; PlantSpecies::COUNT],
+            row_densities: vec![vec![0; size.h as usize]; PlantSpecies::COUNT],
+            walls: vec![false; size.w as usize * size.h as usize],
             size,
+            topology,
             time_since_regenerate: 0.0,
         }
     }
 
+    /// Returns the densities of a plant species, row by row.
     #[inline]
-    pub fn get_density(&self, pos: Vec2i) -> u8 {
-        if pos.x < 0 || pos.y < 0 || pos.x as u32 >= self.size.w || pos.y as u32 >= self.size.h {
-            0
-        } else {
-            self.get_density_unchecked(pos)
+    pub fn densities(&self, species: PlantSpecies) -> &[u8] {
+        &self.densities[species as usize]
+    }
+
+    /// Returns the sum of the densities of a plant species in every row.
+    #[inline]
+    pub fn row_densities(&self, species: PlantSpecies) -> &[u32] {
+        &self.row_densities[species as usize]
+    }
+
+    /// Returns the sum of all densities of a plant species.
+    #[inline]
+    pub fn total_density(&self, species: PlantSpecies) -> u64 {
+        self.total_densities[species as usize]
+    }
+
+    /// Hashes the densities of all species, the walls and the regeneration timer.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = FnvHasher::new();
+        for densities in self.densities.iter() {
+            hasher.write(densities);
         }
+        // Only the indices of wall cells are hashed, so walls don't affect grids without them.
+        for (idx, _) in self.walls.iter().enumerate().filter(|(_, wall)| **wall) {
+            hasher.write_u64(idx as u64);
+        }
+        hasher.write_f32(self.time_since_regenerate);
+
+        hasher.finish()
+    }
+
+    /// Returns the cell for a cell position that may lie outside of the grid.
+    /// On a torus the position wraps around, otherwise positions outside of the grid have no cell.
+    #[inline]
+    pub fn wrap_cell(&self, pos: Vec2i) -> Option<Vec2i> {
+        match self.topology {
+            Topology::Torus => Some(Vec2i::new(
+                pos.x.rem_euclid(self.size.w as i32),
+                pos.y.rem_euclid(self.size.h as i32),
+            )),
+            Topology::Walled => {
+                if pos.x < 0
+                    || pos.y < 0
+                    || pos.x as u32 >= self.size.w
+                    || pos.y as u32 >= self.size.h
+                {
+                    None
+                } else {
+                    Some(pos)
+                }
+            }
+        }
+    }
+
+    /// Returns the cell that contains a position in the world.
+    #[inline]
+    pub fn cell_at(&self, pos: Vec2f) -> Option<Vec2i> {
+        self.wrap_cell(Vec2i::new(pos.x.floor() as i32, pos.y.floor() as i32))
+    }
+
+    /// Moves a position in the world back inside the grid,
+    /// by wrapping around on a torus or by stopping at the walls.
+    pub fn wrap_pos(&self, pos: Vec2f) -> Vec2f {
+        let size = Vec2f::new(self.size.w as f32, self.size.h as f32);
+        match self.topology {
+            Topology::Torus => Vec2f::new(pos.x.rem_euclid(size.x), pos.y.rem_euclid(size.y)),
+            Topology::Walled => Vec2f::new(pos.x.clamped(0.0, size.x), pos.y.clamped(0.0, size.y)),
+        }
+    }
+
+    /// Returns the density at a cell position, which may lie outside of the grid.
+    #[inline]
+    pub fn get_density(&self, species: PlantSpecies, pos: Vec2i) -> u8 {
+        match self.wrap_cell(pos) {
+            Some(cell) => self.get_density_unchecked(species, cell),
+            None => 0,
+        }
+    }
+
+    #[inline]
+    pub fn get_density_unchecked(&self, species: PlantSpecies, pos: Vec2i) -> u8 {
+        self.densities[species as usize][pos.y as usize * self.size.w as usize + pos.x as usize]
+    }
+
+    #[inline]
+    pub fn set_density(&mut self, species: PlantSpecies, pos: Vec2i, density: u8) {
+        let idx = pos.y as usize * self.size.w as usize + pos.x as usize;
+        let old_density = self.densities[species as usize][idx];
+        self.densities[species as usize][idx] = density;
+
+        self.total_densities[species as usize] += density as u64;
+        self.total_densities[species as usize] -= old_density as u64;
+        self.row_densities[species as usize][pos.y as usize] += density as u32;
+        self.row_densities[species as usize][pos.y as usize] -= old_density as u32;
     }
 
+    /// Returns whether a cell is a wall.
     #[inline]
-    pub fn get_density_unchecked(&self, pos: Vec2i) -> u8 {
-        self.densities[pos.y as usize * self.size.w as usize + pos.x as usize]
+    pub fn is_wall(&self, cell: Vec2i) -> bool {
+        self.walls[cell.y as usize * self.size.w as usize + cell.x as usize]
     }
 
+    /// Returns whether a position in the world lies in a wall.
     #[inline]
-    pub fn set_density(&mut self, pos: Vec2i, density: u8) {
-        self.densities[pos.y as usize * self.size.w as usize + pos.x as usize] = density;
+    pub fn is_wall_at(&self, pos: Vec2f) -> bool {
+        matches!(self.cell_at(pos), Some(cell) if self.is_wall(cell))
     }
 
-    fn get_target_total_density(&self) -> u64 {
-        let total_density = self.size.w as f32 * self.size.h as f32 * TARGET_DENSITY_PER_CELL;
+    /// Returns the cells whose centers lie within a radius of a position, for editing the grid
+    /// with a brush. On a torus the brush wraps around, otherwise it stops at the walls.
+    pub fn brush_cells(&self, center: Vec2f, radius: f32) -> Vec<Vec2i> {
+        let min = (center - radius).map(|x| x.floor() as i32);
+        let max = (center + radius).map(|x| x.ceil() as i32);
+        let mut cells = Vec::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell_center = Vec2f::new(x as f32 + 0.5, y as f32 + 0.5);
+                if (cell_center - center).magnitude_squared() > radius * radius {
+                    continue;
+                }
+                if let Some(cell) = self.wrap_cell(Vec2i::new(x, y)) {
+                    cells.push(cell);
+                }
+            }
+        }
+        cells
+    }
+
+    /// Fills the cells within a radius of a position with a plant species, except for walls.
+    pub fn paint(&mut self, species: PlantSpecies, center: Vec2f, radius: f32) {
+        for cell in self.brush_cells(center, radius) {
+            if !self.is_wall(cell) {
+                self.set_density(species, cell, u8::MAX);
+            }
+        }
+    }
+
+    /// Removes all plants within a radius of a position.
+    pub fn erase(&mut self, center: Vec2f, radius: f32) {
+        for cell in self.brush_cells(center, radius) {
+            for species in PlantSpecies::iter() {
+                self.set_density(species, cell, 0);
+            }
+        }
+    }
+
+    /// Places or removes walls within a radius of a position.
+    /// The plants in new walls are removed.
+    pub fn set_walls(&mut self, center: Vec2f, radius: f32, wall: bool) {
+        for cell in self.brush_cells(center, radius) {
+            let idx = cell.y as usize * self.size.w as usize + cell.x as usize;
+            self.walls[idx] = wall;
+            if wall {
+                for species in PlantSpecies::iter() {
+                    self.set_density(species, cell, 0);
+                }
+            }
+        }
+    }
+
+    fn get_target_total_density(&self, species: PlantSpecies) -> u64 {
+        let total_density =
+            self.size.w as f32 * self.size.h as f32 * species.traits().target_density_per_cell;
 
         total_density as u64
     }
 
     pub fn generate(&mut self, rng: &mut WRng) {
-        // Don't let plants grow on the borders for performance reasons.
-        for row in 1..self.size.h-1 {
-            for col in 1..self.size.w-1 {
-                let random_value = rng.gen::<f32>();
-                if random_value > GENERATE_DENSITY_THRESHOLD {
-                    let new_density = (((random_value - GENERATE_DENSITY_THRESHOLD)
-                        / (1.0 - GENERATE_DENSITY_THRESHOLD))
-                        * 255.0) as u8;
-                    self.set_density(Vec2i::new(row as i32, col as i32), new_density);
+        for species in PlantSpecies::iter() {
+            let threshold = species.traits().generate_threshold;
+            for row in 0..self.size.h {
+                for col in 0..self.size.w {
+                    let random_value = rng.gen::<f32>();
+                    if random_value > threshold {
+                        let new_density =
+                            (((random_value - threshold) / (1.0 - threshold)) * 255.0) as u8;
+                        self.set_density(species, Vec2i::new(col as i32, row as i32), new_density);
+                    }
                 }
             }
         }
@@ -78,41 +236,300 @@ impl PlantGrid {
     }
 
     fn regenerate(&mut self, rng: &mut WRng) {
-        let total_density: u64 = self.densities.iter().map(|x| *x as u64).sum();
-        if total_density < self.get_target_total_density() {
-            // Don't let plants grow on the borders for performance reasons.
-            for row in 1..self.size.h-1 {
-                for col in 1..self.size.w-1 {
-                    let mut neighbor_total = 0.0f32;
-                    neighbor_total +=
-                        self.get_density_unchecked(Vec2i::new(col as i32 + 1, row as i32 + 0)) as f32;
-                    neighbor_total +=
-                        self.get_density_unchecked(Vec2i::new(col as i32 + 1, row as i32 + 1)) as f32;
-                    neighbor_total +=
-                        self.get_density_unchecked(Vec2i::new(col as i32 + 0, row as i32 + 1)) as f32;
-                    neighbor_total +=
-                        self.get_density_unchecked(Vec2i::new(col as i32 - 1, row as i32 + 1)) as f32;
-                    neighbor_total +=
-                        self.get_density_unchecked(Vec2i::new(col as i32 - 1, row as i32 + 0)) as f32;
-                    neighbor_total +=
-                        self.get_density_unchecked(Vec2i::new(col as i32 - 1, row as i32 - 1)) as f32;
-                    neighbor_total +=
-                        self.get_density_unchecked(Vec2i::new(col as i32 + 0, row as i32 - 1)) as f32;
-                    neighbor_total +=
-                        self.get_density_unchecked(Vec2i::new(col as i32 + 1, row as i32 - 1)) as f32;
-
-                    let pos = Vec2i::new(col as i32, row as i32);
-                    if neighbor_total > REGENERATE_NEIGHBOR_THRESHOLD {
-                        let mut new_density = self.get_density_unchecked(pos) as f32;
-                        new_density += REGENERATE_INCREMENT_MAX * rng.gen::<f32>();
-                        if new_density > 255.0 {
-                            new_density = 255.0;
+        for species in PlantSpecies::iter() {
+            self.regenerate_species(species, rng);
+        }
+    }
+
+    /// Grows plants in every cell whose eight neighbors are dense enough.
+    /// Cells are updated in place in row-major order, so a cell sees the new densities of the
+    /// neighbors before it. The neighbor totals are computed with a sliding window of column
+    /// sums, and rows whose surroundings are too sparse to grow anything are skipped entirely.
+    /// On a torus the neighbors wrap around, otherwise cells beyond the walls count as empty.
+    /// Nothing grows in walls, and as they are empty they don't help their neighbors grow.
+    fn regenerate_species(&mut self, species: PlantSpecies, rng: &mut WRng) {
+        let traits = species.traits();
+        if self.total_density(species) >= self.get_target_total_density(species) {
+            return;
+        }
+
+        let width = self.size.w as usize;
+        let height = self.size.h as usize;
+        let torus = self.topology == Topology::Torus;
+        let walls = &self.walls;
+        let densities = &mut self.densities[species as usize];
+        let row_densities = &mut self.row_densities[species as usize];
+        let mut total_density = self.total_densities[species as usize];
+        let mut above = vec![0u8; width];
+        let mut below = vec![0u8; width];
+
+        for row in 0..height {
+            // The neighboring rows, or None if they lie beyond a wall.
+            let above_row = match row {
+                0 if torus => Some(height - 1),
+                0 => None,
+                _ => Some(row - 1),
+            };
+            let below_row = match row + 1 {
+                next_row if next_row < height => Some(next_row),
+                _ if torus => Some(0),
+                _ => None,
+            };
+
+            let band_density = above_row.map_or(0, |idx| row_densities[idx])
+                + row_densities[row]
+                + below_row.map_or(0, |idx| row_densities[idx]);
+            if band_density as f32 <= traits.regenerate_neighbor_threshold {
+                // No cell in this row can have enough neighbors.
+                continue;
+            }
+
+            copy_row(densities, width, above_row, &mut above);
+            copy_row(densities, width, below_row, &mut below);
+            let current = &mut densities[row * width..(row + 1) * width];
+            let column_density = |current: &[u8], col: usize| {
+                above[col] as u32 + current[col] as u32 + below[col] as u32
+            };
+
+            let mut row_density = row_densities[row];
+            let mut left_column = if torus {
+                column_density(current, width - 1)
+            } else {
+                0
+            };
+            let mut center_column = column_density(current, 0);
+            for col in 0..width {
+                let right_column = if col + 1 < width {
+                    column_density(current, col + 1)
+                } else if torus {
+                    column_density(current, 0)
+                } else {
+                    0
+                };
+                let density = current[col];
+                let neighbor_total = left_column + center_column + right_column - density as u32;
+
+                if neighbor_total as f32 > traits.regenerate_neighbor_threshold
+                    && !walls[row * width + col]
+                {
+                    let mut new_density = density as f32;
+                    new_density += traits.regenerate_increment_max * rng.gen::<f32>();
+                    if new_density > 255.0 {
+                        new_density = 255.0;
+                    }
+                    let growth = new_density as u8 - density;
+                    current[col] += growth;
+                    center_column += growth as u32;
+                    row_density += growth as u32;
+                    total_density += growth as u64;
+                }
+
+                left_column = center_column;
+                center_column = right_column;
+            }
+            row_densities[row] = row_density;
+        }
+
+        self.total_densities[species as usize] = total_density;
+    }
+}
+
+/// Copies a row of densities into a buffer, or clears the buffer if there is no such row.
+fn copy_row(densities: &[u8], width: usize, row: Option<usize>, buffer: &mut [u8]) {
+    match row {
+        Some(row) => buffer.copy_from_slice(&densities[row * width..(row + 1) * width]),
+        None => buffer.iter_mut().for_each(|density| *density = 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn torus_wraps_cells_and_positions() {
+        let plant_grid = PlantGrid::new(Size2i::new(10, 20), Topology::Torus);
+        assert_eq!(
+            plant_grid.wrap_cell(Vec2i::new(-1, 20)),
+            Some(Vec2i::new(9, 0))
+        );
+        assert_eq!(
+            plant_grid.cell_at(Vec2f::new(-0.5, 40.5)),
+            Some(Vec2i::new(9, 0))
+        );
+        assert_eq!(
+            plant_grid.wrap_pos(Vec2f::new(-1.0, 21.0)),
+            Vec2f::new(9.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn walls_block_cells_and_positions() {
+        let plant_grid = PlantGrid::new(Size2i::new(10, 20), Topology::Walled);
+        assert_eq!(plant_grid.wrap_cell(Vec2i::new(-1, 5)), None);
+        assert_eq!(plant_grid.cell_at(Vec2f::new(-0.5, 5.0)), None);
+        assert_eq!(
+            plant_grid.wrap_cell(Vec2i::new(9, 19)),
+            Some(Vec2i::new(9, 19))
+        );
+        assert_eq!(
+            plant_grid.wrap_pos(Vec2f::new(-1.0, 21.0)),
+            Vec2f::new(0.0, 20.0)
+        );
+    }
+
+    /// Fills the cells in the bottom-left corner and lets the grass grow for a while.
+    fn grow_from_corner(topology: Topology) -> PlantGrid {
+        let mut plant_grid = PlantGrid::new(Size2i::new(10, 10), topology);
+        for pos in [Vec2i::new(0, 0), Vec2i::new(1, 0), Vec2i::new(0, 1)].iter() {
+            plant_grid.set_density(PlantSpecies::Grass, *pos, 255);
+        }
+        let mut rng = WRng::seed_from_u64(0);
+        for _ in 0..10 {
+            plant_grid.regenerate_species(PlantSpecies::Grass, &mut rng);
+        }
+        plant_grid
+    }
+
+    #[test]
+    fn plants_grow_across_torus_borders() {
+        let plant_grid = grow_from_corner(Topology::Torus);
+        assert!(plant_grid.get_density_unchecked(PlantSpecies::Grass, Vec2i::new(9, 9)) > 0);
+        assert!(plant_grid.get_density_unchecked(PlantSpecies::Grass, Vec2i::new(1, 1)) > 0);
+    }
+
+    #[test]
+    fn plants_do_not_grow_through_walls() {
+        let plant_grid = grow_from_corner(Topology::Walled);
+        assert_eq!(
+            plant_grid.get_density_unchecked(PlantSpecies::Grass, Vec2i::new(9, 9)),
+            0
+        );
+        assert!(plant_grid.get_density_unchecked(PlantSpecies::Grass, Vec2i::new(1, 1)) > 0);
+    }
+
+    #[test]
+    fn brushes_wrap_around_and_nothing_grows_in_walls() {
+        let mut plant_grid = PlantGrid::new(Size2i::new(50, 50), Topology::Torus);
+        let cells = plant_grid.brush_cells(Vec2f::new(0.5, 0.5), 1.0);
+        assert_eq!(cells.len(), 5);
+        assert!(cells.contains(&Vec2i::new(49, 0)));
+        assert!(cells.contains(&Vec2i::new(0, 49)));
+
+        let center = Vec2f::new(25.5, 25.5);
+        plant_grid.paint(PlantSpecies::Grass, center, 3.0);
+        let hash = plant_grid.state_hash();
+        plant_grid.set_walls(center, 0.5, true);
+        assert!(plant_grid.is_wall(Vec2i::new(25, 25)));
+        assert!(plant_grid.is_wall_at(center));
+        assert_ne!(plant_grid.state_hash(), hash);
+
+        let mut rng = WRng::seed_from_u64(0);
+        for _ in 0..10 {
+            plant_grid.regenerate_species(PlantSpecies::Grass, &mut rng);
+        }
+        assert_eq!(
+            plant_grid.get_density_unchecked(PlantSpecies::Grass, Vec2i::new(25, 25)),
+            0
+        );
+        assert_eq!(
+            plant_grid.get_density_unchecked(PlantSpecies::Grass, Vec2i::new(26, 25)),
+            255
+        );
+
+        plant_grid.set_walls(center, 0.5, false);
+        plant_grid.erase(center, 3.0);
+        assert!(!plant_grid.is_wall(Vec2i::new(25, 25)));
+        assert_eq!(
+            plant_grid.get_density_unchecked(PlantSpecies::Grass, Vec2i::new(26, 25)),
+            0
+        );
+    }
+
+    /// Regenerates a species like `regenerate_species`, but recounts the eight neighbors of
+    /// every cell one by one.
+    fn regenerate_by_recount(plant_grid: &mut PlantGrid, species: PlantSpecies, rng: &mut WRng) {
+        if plant_grid.total_density(species) >= plant_grid.get_target_total_density(species) {
+            return;
+        }
+        let traits = species.traits();
+        for row in 0..plant_grid.size.h as i32 {
+            for col in 0..plant_grid.size.w as i32 {
+                let pos = Vec2i::new(col, row);
+                let mut neighbor_total = 0.0f32;
+                for y in -1..=1 {
+                    for x in -1..=1 {
+                        if x != 0 || y != 0 {
+                            let neighbor = pos + Vec2i::new(x, y);
+                            neighbor_total += plant_grid.get_density(species, neighbor) as f32;
                         }
-                        self.set_density(pos, new_density as u8);
                     }
+                }
+                if neighbor_total > traits.regenerate_neighbor_threshold && !plant_grid.is_wall(pos)
+                {
+                    let density = plant_grid.get_density_unchecked(species, pos) as f32;
+                    let new_density = density + traits.regenerate_increment_max * rng.gen::<f32>();
+                    plant_grid.set_density(species, pos, new_density.min(255.0) as u8);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sliding_window_matches_recount() {
+        for topology in [Topology::Torus, Topology::Walled].iter() {
+            let mut rng = WRng::seed_from_u64(5);
+            let mut plant_grid = PlantGrid::new(Size2i::new(30, 20), *topology);
+            for species in PlantSpecies::iter() {
+                for row in 0..20 {
+                    for col in 0..30 {
+                        if rng.gen::<f32>() < 0.01 {
+                            let pos = Vec2i::new(col, row);
+                            plant_grid.set_density(species, pos, rng.gen());
+                        }
+                    }
+                }
+            }
+            // Plants next to the borders, whose neighbors on a torus lie across them.
+            plant_grid.set_density(PlantSpecies::Grass, Vec2i::new(29, 5), 255);
+            plant_grid.set_density(PlantSpecies::Grass, Vec2i::new(0, 12), 255);
+            plant_grid.set_density(PlantSpecies::Grass, Vec2i::new(8, 19), 255);
+            plant_grid.set_density(PlantSpecies::Grass, Vec2i::new(22, 0), 255);
+            plant_grid.set_walls(Vec2f::new(15.0, 10.0), 2.0, true);
+            let initial_total = plant_grid.total_density(PlantSpecies::Grass);
 
+            let mut recounted_grid = plant_grid.clone();
+            let mut recounted_rng = rng.clone();
+            for _ in 0..5 {
+                for species in PlantSpecies::iter() {
+                    plant_grid.regenerate_species(species, &mut rng);
+                    regenerate_by_recount(&mut recounted_grid, species, &mut recounted_rng);
                 }
             }
+
+            assert!(plant_grid.total_density(PlantSpecies::Grass) > initial_total);
+            for species in PlantSpecies::iter() {
+                assert!(plant_grid.densities(species) == recounted_grid.densities(species));
+                assert_eq!(
+                    plant_grid.total_density(species),
+                    recounted_grid.total_density(species)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn running_totals_match_densities() {
+        let mut plant_grid = PlantGrid::new(Size2i::new(50, 40), Topology::Torus);
+        plant_grid.generate(&mut WRng::seed_from_u64(3));
+        for species in PlantSpecies::iter() {
+            let total: u64 = plant_grid
+                .densities(species)
+                .iter()
+                .map(|x| *x as u64)
+                .sum();
+            assert_eq!(plant_grid.total_density(species), total);
         }
     }
 }
@@ -0,0 +1,58 @@
+use strum_macros::{AsRefStr, EnumCount as EnumCountMacro, EnumIter};
+
+#[derive(Copy, Clone, PartialEq, EnumCountMacro, EnumIter, AsRefStr)]
+pub enum PlantSpecies {
+    Grass = 0,
+    Berries = 1,
+    Poison = 2,
+}
+
+/// Attributes of a plant species, such as how it grows and how nutritious it is.
+pub struct SpeciesTraits {
+    /// Energy multiplier when eaten. Negative values hurt the agent.
+    pub nutrition: f32,
+    pub color: [u8; 3],
+    /// Random values above this threshold seed a plant cell during generation.
+    pub generate_threshold: f32,
+    /// Minimum total density of the eight neighbors before a cell can grow.
+    pub regenerate_neighbor_threshold: f32,
+    pub regenerate_increment_max: f32,
+    pub target_density_per_cell: f32,
+}
+
+const GRASS_TRAITS: SpeciesTraits = SpeciesTraits {
+    nutrition: 1.0,
+    color: [96, 255, 32],
+    generate_threshold: 0.995,
+    regenerate_neighbor_threshold: 100.0,
+    regenerate_increment_max: 3.0,
+    target_density_per_cell: 8.0,
+};
+
+const BERRIES_TRAITS: SpeciesTraits = SpeciesTraits {
+    nutrition: 2.5,
+    color: [220, 40, 120],
+    generate_threshold: 0.998,
+    regenerate_neighbor_threshold: 150.0,
+    regenerate_increment_max: 1.0,
+    target_density_per_cell: 2.0,
+};
+
+const POISON_TRAITS: SpeciesTraits = SpeciesTraits {
+    nutrition: -2.0,
+    color: [150, 60, 255],
+    generate_threshold: 0.998,
+    regenerate_neighbor_threshold: 100.0,
+    regenerate_increment_max: 2.0,
+    target_density_per_cell: 3.0,
+};
+
+impl PlantSpecies {
+    pub fn traits(self) -> &'static SpeciesTraits {
+        match self {
+            PlantSpecies::Grass => &GRASS_TRAITS,
+            PlantSpecies::Berries => &BERRIES_TRAITS,
+            PlantSpecies::Poison => &POISON_TRAITS,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::util::FnvHasher;
+use std::hash::Hasher;
+
+/// Counters and records that describe the history of a world.
+#[derive(Clone)]
+pub struct Statistics {
+    pub births: u64,
+    pub deaths: u64,
+    /// Number of random agents that were injected after the start.
+    pub immigrants: u64,
+    /// Number of times all agents died, before any immigrants arrived.
+    pub extinctions: u64,
+    pub max_time_alive: f32,
+    pub max_generation: u32,
+}
+
+impl Default for Statistics {
+    fn default() -> Statistics {
+        Statistics::new()
+    }
+}
+
+impl Statistics {
+    pub fn new() -> Statistics {
+        Statistics {
+            births: 0,
+            deaths: 0,
+            immigrants: 0,
+            extinctions: 0,
+            max_time_alive: 0.0,
+            max_generation: 1,
+        }
+    }
+
+    /// Feeds the statistics into a state hash.
+    pub fn hash_state(&self, hasher: &mut FnvHasher) {
+        hasher.write_u64(self.births);
+        hasher.write_u64(self.deaths);
+        hasher.write_u64(self.immigrants);
+        hasher.write_u64(self.extinctions);
+        hasher.write_f32(self.max_time_alive);
+        hasher.write_u32(self.max_generation);
+    }
+}
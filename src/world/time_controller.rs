@@ -1,50 +1,218 @@
-use crate::world::{Params, World};
+use crate::util::time_ns;
+use crate::world::{Edit, EndMonitor, EndReason, Params, World};
+use std::collections::VecDeque;
+
+/// A time travel in progress.
+struct Travel {
+    /// The time of the world when the travel started, after going back to the beginning.
+    from: f64,
+    to: f64,
+    /// The edits made by hand that are yet to be replayed, oldest first.
+    edits: VecDeque<(f64, Edit)>,
+}
+
+impl Travel {
+    /// Replays the edits that were made at or before the current time of the world.
+    fn replay_edits(&mut self, params: &Params, world: &mut World) {
+        while let Some((time, _)) = self.edits.front() {
+            if *time > world.time {
+                break;
+            }
+            let (_, edit) = self.edits.pop_front().unwrap();
+            world.edit(params, edit);
+        }
+    }
+}
 
 /// A struct that handles calling the world.tick() method
 /// the right amount of times. Also provides time travel.
 pub struct TimeController {
     time_left: f32,
+    travel: Option<Travel>,
 }
 
 impl TimeController {
     pub fn new() -> TimeController {
-        TimeController { time_left: 0.0 }
+        TimeController {
+            time_left: 0.0,
+            travel: None,
+        }
     }
 
-    /// Run the world for the specified amount of time.
-    pub fn tick(&mut self, params: &Params, world: &mut World, d_time: f32) {
+    /// Run the world for the specified amount of time, calling `after_tick` after every tick.
+    pub fn tick(
+        &mut self,
+        params: &Params,
+        world: &mut World,
+        d_time: f32,
+        mut after_tick: impl FnMut(&World),
+    ) {
         self.time_left += d_time;
         while self.time_left > params.tick_interval {
             world.tick(params, params.tick_interval);
             self.time_left -= params.tick_interval;
+            after_tick(world);
         }
     }
 
+    /// Run the world for the specified amount of time, but stop as soon as
+    /// one of the end conditions is met. Calls `after_tick` after every tick.
+    pub fn tick_until_end(
+        &mut self,
+        params: &Params,
+        world: &mut World,
+        d_time: f32,
+        monitor: &mut EndMonitor,
+        mut after_tick: impl FnMut(&World),
+    ) -> Option<EndReason> {
+        self.time_left += d_time;
+        while self.time_left > params.tick_interval {
+            world.tick(params, params.tick_interval);
+            self.time_left -= params.tick_interval;
+            after_tick(world);
+            if let Some(reason) = monitor.check(world, &params.end) {
+                self.time_left = 0.0;
+                return Some(reason);
+            }
+        }
+
+        None
+    }
+
     /// Travel to the specified time.
     pub fn goto(&mut self, params: &Params, world: &mut World, new_time: f64) {
+        self.start_goto(params, world, new_time);
+        while !self.continue_goto(params, world, u128::MAX) {}
+    }
+
+    /// Start travelling to the specified time. The travel itself happens in
+    /// `continue_goto`, so a window can stay responsive while it runs.
+    /// Travelling to the past replays the edits made by hand up to the new time,
+    /// and forgets the edits made after it.
+    pub fn start_goto(&mut self, params: &Params, world: &mut World, new_time: f64) {
+        let mut edits = VecDeque::new();
         if new_time < world.time {
             // If the time is in the past,
             // create a new world and run the simulation again.
+            edits = world.take_edits().into_iter().collect();
+            let log_records = world.log_records;
             *world = World::new_seeded(params, world.seed);
+            world.log_records = log_records;
         }
-        while world.time < new_time {
+        self.time_left = 0.0;
+        self.travel = Some(Travel {
+            from: world.time,
+            to: new_time,
+            edits,
+        });
+    }
+
+    /// Continue the current time travel until the target time or the deadline
+    /// (as returned by `time_ns`) is reached.
+    /// Returns true if there is no time travel left to do.
+    pub fn continue_goto(&mut self, params: &Params, world: &mut World, deadline: u128) -> bool {
+        let travel = match self.travel.as_mut() {
+            Some(travel) => travel,
+            None => return true,
+        };
+        while world.time < travel.to {
+            if time_ns() >= deadline {
+                return false;
+            }
+            travel.replay_edits(params, world);
             world.tick(params, params.tick_interval);
         }
+        travel.replay_edits(params, world);
+        self.travel = None;
+        true
     }
 
-    /// Prompt the user on the command-line for time travel.
-    pub fn goto_prompt(&mut self, params: &Params, world: &mut World) {
-        println!("enter new time:");
-        let mut line = String::new();
-        std::io::stdin().read_line(&mut line).unwrap();
-        line = line.trim_end().to_string();
-        match line.parse::<f64>() {
-            Ok(new_time) => {
-                self.goto(params, world, new_time);
+    /// Returns how far the current time travel has come, from 0 to 1,
+    /// or `None` if there is no time travel in progress.
+    pub fn goto_progress(&self, world: &World) -> Option<f64> {
+        self.travel.as_ref().map(|travel| {
+            if travel.to <= travel.from {
+                1.0
+            } else {
+                ((world.time - travel.from) / (travel.to - travel.from)).min(1.0)
             }
-            Err(..) => {
-                println!("{} is not a valid time", line);
-            }
-        }
+        })
+    }
+
+    /// Stop the current time travel. The world stays at the time it has reached,
+    /// and edits made after it are forgotten.
+    pub fn cancel_goto(&mut self) {
+        self.travel = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Vec2f;
+
+    #[test]
+    fn time_travel_runs_in_steps_and_can_be_cancelled() {
+        let params = Params::default();
+        let mut time_controller = TimeController::new();
+        let mut world = World::new_seeded(&params, 1);
+
+        time_controller.start_goto(&params, &mut world, 10.0);
+        assert!(!time_controller.continue_goto(&params, &mut world, 0));
+        assert_eq!(time_controller.goto_progress(&world), Some(0.0));
+        world.tick(&params, params.tick_interval);
+        assert!(time_controller.goto_progress(&world).unwrap() > 0.0);
+        assert!(time_controller.continue_goto(&params, &mut world, u128::MAX));
+        assert!(world.time >= 10.0);
+        assert_eq!(time_controller.goto_progress(&world), None);
+
+        time_controller.start_goto(&params, &mut world, 5.0);
+        assert_eq!(world.time, 0.0);
+        time_controller.cancel_goto();
+        assert!(time_controller.continue_goto(&params, &mut world, u128::MAX));
+        assert_eq!(world.time, 0.0);
+    }
+
+    #[test]
+    fn travelling_back_replays_edits() {
+        let params = Params::default();
+        let mut time_controller = TimeController::new();
+        let mut world = World::new_seeded(&params, 1);
+        world.log_records = false;
+        let center = Vec2f::new(50.0, 50.0);
+
+        time_controller.goto(&params, &mut world, 1.0);
+        world.edit(
+            &params,
+            Edit::SetWalls {
+                center,
+                radius: 4.0,
+                wall: true,
+            },
+        );
+        time_controller.goto(&params, &mut world, 2.0);
+        let spawn = Edit::Spawn {
+            genome: None,
+            pos: center,
+        };
+        let id = world.edit(&params, spawn).unwrap();
+        time_controller.goto(&params, &mut world, 3.0);
+        world.edit(&params, Edit::Remove { id });
+        time_controller.goto(&params, &mut world, 5.0);
+        let hash = world.state_hash();
+
+        // Replaying all edits leads to the same world.
+        time_controller.goto(&params, &mut world, 4.0);
+        time_controller.goto(&params, &mut world, 5.0);
+        assert_eq!(world.state_hash(), hash);
+        assert!(!world.log_records);
+
+        // Going back to before the removal keeps the walls and the spawned agent,
+        // and forgets the removal.
+        time_controller.goto(&params, &mut world, 2.5);
+        assert!(world.plant_grid.is_wall_at(center));
+        assert!(world.agent_by_id(id).is_some());
+        time_controller.goto(&params, &mut world, 5.0);
+        assert!(world.agent_by_id(id).is_some());
     }
 }
@@ -1,18 +1,37 @@
-use crate::util::{time_ns, vec2f_to_vec2i, WRng};
-use crate::world::{Agent, Params, PlantGrid};
+use crate::util::{hash_rng, time_ns, FnvHasher, Vec2f, WRng};
+use crate::world::{
+    Agent, Edit, Founder, Genome, GrazingMap, History, Immigration, Origin, Params, PlantGrid,
+    PlantSpecies, Statistics,
+};
 use rand::SeedableRng;
+use rayon::prelude::*;
+use std::hash::Hasher;
+use strum::IntoEnumIterator;
 
 /// A universe in which everything resides.
 /// Contains a plant grid and a number of agents.
-/// Keeps track of the current (simulation) time and various agent records.
+/// Keeps track of the current (simulation) time, various agent statistics, a history and
+/// where agents have eaten.
 pub struct World {
     pub agents: Vec<Agent>,
     pub plant_grid: PlantGrid,
     pub time: f64, // 64-bit required for precision after ~1 million seconds.
     pub seed: u64,
+    pub statistics: Statistics,
+    pub history: History,
+    pub grazing: GrazingMap,
+    /// Whether new records are printed as they happen.
+    pub log_records: bool,
+    /// The edits made by hand and the times they were made at, oldest first.
+    edits: Vec<(f64, Edit)>,
     rng: WRng,
-    max_time_alive: f32,
-    max_generation: u32,
+    /// The lineage of the next founder or immigrant.
+    next_lineage: u32,
+    /// The id of the next agent. Replayed edits find agents by id, but ids are assigned
+    /// deterministically in the order agents are created, so they need no hash of their own.
+    next_agent_id: u64,
+    /// Fractional immigrants accumulated by `Immigration::Rate`.
+    immigration_progress: f32,
 }
 
 impl World {
@@ -37,70 +56,574 @@ impl World {
     pub fn new_seeded(params: &Params, seed: u64) -> World {
         let mut rng = WRng::seed_from_u64(seed);
 
-        let mut plant_grid = PlantGrid::new(params.plant_grid_size);
+        let mut plant_grid = PlantGrid::new(params.plant_grid_size, params.topology);
         plant_grid.generate(&mut rng);
-        let mut agents = Vec::with_capacity(params.agent_count as usize);
-        for _ in 0..params.agent_count {
-            agents.push(Agent::new_random(&params, &mut rng));
-        }
-        World {
-            agents: agents,
+        let mut world = World {
+            agents: Vec::with_capacity(params.agent_count as usize),
             plant_grid,
             time: 0.0,
             seed,
+            statistics: Statistics::new(),
+            history: History::new(),
+            grazing: GrazingMap::new(params.plant_grid_size),
+            log_records: true,
+            edits: Vec::new(),
             rng,
-            max_time_alive: 0.0,
-            max_generation: 1,
+            next_lineage: 0,
+            next_agent_id: 0,
+            immigration_progress: 0.0,
+        };
+        if params.founders.is_empty() {
+            for idx in 0..params.agent_count as usize {
+                if params.genomes.is_empty() {
+                    world.add_random_agent(params, Origin::Founder);
+                } else {
+                    world.add_genome_agent(params, &params.genomes[idx % params.genomes.len()]);
+                }
+            }
+        } else {
+            for founder in params.founders.iter() {
+                for _ in 0..founder.count {
+                    world.add_founder(params, founder);
+                }
+            }
         }
+
+        world
+    }
+
+    /// Returns the genomes of all living agents.
+    pub fn export_genomes(&self) -> Vec<Genome> {
+        self.agents
+            .iter()
+            .map(|agent| Genome::from_agent(agent, self.seed, self.time))
+            .collect()
+    }
+
+    /// Returns the living agent with the given id.
+    pub fn agent_by_id(&self, id: u64) -> Option<&Agent> {
+        self.agents.iter().find(|agent| agent.id == id)
+    }
+
+    /// Gives an agent its id and adds it to the world.
+    fn add_agent(&mut self, mut agent: Agent) {
+        agent.id = self.next_agent_id;
+        self.next_agent_id += 1;
+        self.agents.push(agent);
+    }
+
+    /// Adds a random agent that starts a new lineage.
+    fn add_random_agent(&mut self, params: &Params, origin: Origin) {
+        let agent = Agent::new_random(params, origin, self.next_lineage, &mut self.rng);
+        self.add_agent(agent);
+        self.next_lineage += 1;
+        if origin == Origin::Immigrant {
+            self.statistics.immigrants += 1;
+        }
+    }
+
+    /// Adds a founder created from an imported genome, that starts a new lineage.
+    fn add_genome_agent(&mut self, params: &Params, genome: &Genome) {
+        let agent = Agent::from_genome(
+            genome,
+            params,
+            Origin::Founder,
+            self.next_lineage,
+            &mut self.rng,
+        );
+        self.add_agent(agent);
+        self.next_lineage += 1;
+    }
+
+    /// Adds an agent from the founder list, that starts a new lineage.
+    fn add_founder(&mut self, params: &Params, founder: &Founder) {
+        let mut agent = match &founder.genome {
+            Some(genome) => Agent::from_genome(
+                genome,
+                params,
+                Origin::Founder,
+                self.next_lineage,
+                &mut self.rng,
+            ),
+            None => Agent::new_random(params, Origin::Founder, self.next_lineage, &mut self.rng),
+        };
+        if let Some(pos) = founder.pos {
+            agent.pos = self.plant_grid.wrap_pos(pos);
+        }
+        if let Some(angle) = founder.angle {
+            agent.angle = angle;
+        }
+        self.add_agent(agent);
+        self.next_lineage += 1;
+    }
+
+    /// Adds an agent at a position by hand, as an immigrant that starts a new lineage.
+    /// It is created from a genome, or at random if there is none. Returns the id of the agent.
+    /// The carrying capacity is ignored, so agents can always be added by hand, but the agent is
+    /// counted as an immigrant.
+    pub fn spawn_agent(&mut self, params: &Params, genome: Option<&Genome>, pos: Vec2f) -> u64 {
+        let mut agent = match genome {
+            Some(genome) => Agent::from_genome(
+                genome,
+                params,
+                Origin::Immigrant,
+                self.next_lineage,
+                &mut self.rng,
+            ),
+            None => Agent::new_random(params, Origin::Immigrant, self.next_lineage, &mut self.rng),
+        };
+        agent.pos = self.plant_grid.wrap_pos(pos);
+        let id = self.next_agent_id;
+        self.add_agent(agent);
+        self.next_lineage += 1;
+        self.statistics.immigrants += 1;
+        id
+    }
+
+    /// Removes a living agent by hand. It is not counted as a death.
+    /// Returns whether the agent was found.
+    pub fn remove_agent(&mut self, id: u64) -> bool {
+        let count = self.agents.len();
+        self.agents.retain(|agent| agent.id != id);
+        self.agents.len() < count
+    }
+
+    /// Moves a living agent to a position by hand. It may be put inside a wall.
+    pub fn move_agent(&mut self, id: u64, pos: Vec2f) {
+        let pos = self.plant_grid.wrap_pos(pos);
+        if let Some(agent) = self.agents.iter_mut().find(|agent| agent.id == id) {
+            agent.pos = pos;
+        }
+    }
+
+    /// Applies an edit made by hand, and keeps it in the edit log so time travel can replay it.
+    /// Returns the id of the agent that was spawned, if any.
+    pub fn edit(&mut self, params: &Params, edit: Edit) -> Option<u64> {
+        let spawned = match &edit {
+            Edit::Paint {
+                species,
+                center,
+                radius,
+            } => {
+                self.plant_grid.paint(*species, *center, *radius);
+                None
+            }
+            Edit::Erase { center, radius } => {
+                self.plant_grid.erase(*center, *radius);
+                None
+            }
+            Edit::SetWalls {
+                center,
+                radius,
+                wall,
+            } => {
+                self.plant_grid.set_walls(*center, *radius, *wall);
+                None
+            }
+            Edit::Spawn { genome, pos } => Some(self.spawn_agent(params, genome.as_ref(), *pos)),
+            Edit::Remove { id } => {
+                self.remove_agent(*id);
+                None
+            }
+            Edit::Move { id, pos } => {
+                self.move_agent(*id, *pos);
+                None
+            }
+        };
+        self.edits.push((self.time, edit));
+        spawned
+    }
+
+    /// Returns the edits made by hand and the times they were made at, oldest first.
+    pub fn edits(&self) -> &[(f64, Edit)] {
+        &self.edits
+    }
+
+    /// Takes the edit log, leaving it empty.
+    pub fn take_edits(&mut self) -> Vec<(f64, Edit)> {
+        std::mem::take(&mut self.edits)
+    }
+
+    /// Returns whether the population has reached the carrying capacity.
+    fn is_at_capacity(&self, params: &Params) -> bool {
+        match params.carrying_capacity {
+            Some(capacity) => self.agents.len() >= capacity as usize,
+            None => false,
+        }
+    }
+
+    /// Adds immigrants according to the immigration policy.
+    fn immigrate(&mut self, params: &Params, d_time: f32) {
+        match params.immigration {
+            Immigration::None => {}
+            Immigration::Rate => {
+                self.immigration_progress += params.immigration_rate * d_time;
+                while self.immigration_progress >= 1.0 {
+                    self.immigration_progress -= 1.0;
+                    if !self.is_at_capacity(params) {
+                        self.add_random_agent(params, Origin::Immigrant);
+                    }
+                }
+            }
+            Immigration::Floor => {
+                while self.agents.len() < params.agent_count as usize
+                    && !self.is_at_capacity(params)
+                {
+                    self.add_random_agent(params, Origin::Immigrant);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of living agents that descend from founders and from immigrants.
+    pub fn population_by_origin(&self) -> (usize, usize) {
+        let immigrants = self
+            .agents
+            .iter()
+            .filter(|agent| agent.origin == Origin::Immigrant)
+            .count();
+        (self.agents.len() - immigrants, immigrants)
+    }
+
+    /// Returns the living agents as founders, so a new world can start where this one is.
+    pub fn export_founders(&self) -> Vec<Founder> {
+        self.agents
+            .iter()
+            .map(|agent| Founder::from_agent(agent, self.seed, self.time))
+            .collect()
+    }
+
+    /// Hashes the complete state of the world: agents, plant grid, random number generator,
+    /// time and records. Two worlds with the same state hash will evolve identically.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = FnvHasher::new();
+        hasher.write_u64(self.time.to_bits());
+        hasher.write_u64(self.seed);
+        hasher.write_u64(self.agents.len() as u64);
+        for agent in self.agents.iter() {
+            hasher.write_u64(agent.state_hash());
+        }
+        hasher.write_u64(self.plant_grid.state_hash());
+        hash_rng(&self.rng, &mut hasher);
+        self.statistics.hash_state(&mut hasher);
+        hasher.write_u32(self.next_lineage);
+        hasher.write_f32(self.immigration_progress);
+
+        hasher.finish()
     }
 
     /// Run the world for the specified amount of time.
+    ///
+    /// A tick happens in the following order:
+    /// 1. The plant grid grows.
+    /// 2. All agents sense, think and move in parallel.
+    /// 3. In order, all agents eat, age and decide whether they die or reproduce.
+    ///    An agent eats from the plant grid as left behind by the agents before it.
+    ///    Where it eats is recorded in the grazing map.
+    /// 4. Dead agents are removed, keeping the order of the survivors.
+    /// 5. Newborns are appended in the order of their parents.
+    ///    They are not ticked until the next tick.
+    /// 6. Immigrants are added according to `params.immigration`.
+    ///    If all agents died, this is counted as an extinction first.
+    ///
+    /// Before the tick, the history takes a sample if it is time for one.
+    /// Newborns and immigrants beyond the carrying capacity are not added.
+    /// Births, deaths and immigration only happen if `params.evolution` is enabled.
+    /// The result does not depend on the number of threads.
     pub fn tick(&mut self, params: &Params, d_time: f32) {
+        let was_populated = !self.agents.is_empty();
+        self.history
+            .update(self.time, &self.agents, &self.plant_grid);
         self.plant_grid.tick(d_time, &mut self.rng);
 
-        let mut idx: i32 = 0;
-        while idx < self.agents.len() as i32 {
-            let agent = &mut self.agents[idx as usize];
-            let tick_result = agent.tick(&self.plant_grid, d_time);
-            if tick_result.eat {
-                self.plant_grid
-                    .set_density(vec2f_to_vec2i(agent.get_mouth_pos()), 0);
+        let plant_grid = &self.plant_grid;
+        let moved_forward: Vec<bool> = self
+            .agents
+            .par_iter_mut()
+            .map(|agent| agent.sense_and_move(plant_grid, d_time))
+            .collect();
+
+        let mut alive = vec![true; self.agents.len()];
+        let mut newborns = Vec::new();
+        for (idx, moved_forward) in moved_forward.into_iter().enumerate() {
+            let agent = &mut self.agents[idx];
+            let tick_result = agent.resolve(moved_forward, &self.plant_grid, d_time);
+            if let Some(mouth_cell) = self.plant_grid.cell_at(agent.get_mouth_pos()) {
+                for species in PlantSpecies::iter() {
+                    if tick_result.eat[species as usize] {
+                        self.plant_grid.set_density(species, mouth_cell, 0);
+                    }
+                }
+                if tick_result.eat.iter().any(|eat| *eat) {
+                    self.grazing.record(
+                        mouth_cell,
+                        self.time,
+                        agent.lineage,
+                        tick_result.energy_eaten,
+                    );
+                }
             }
 
             if params.evolution {
                 if tick_result.die {
-                    if agent.time_alive > self.max_time_alive {
-                        self.max_time_alive = agent.time_alive;
-                        println!(
-                            "[{}] new time alive record: {}",
-                            self.time.floor(),
-                            self.max_time_alive
-                        );
-                    }
-                    if agent.generation > self.max_generation {
-                        self.max_generation = agent.generation;
-                        println!(
-                            "[{}] new generation record: {}",
-                            self.time.floor(),
-                            self.max_generation
-                        );
-                    }
-
-                    self.agents.remove(idx as usize);
-                    idx -= 1;
-
-                    if self.agents.len() < params.agent_count as usize {
-                        self.agents.push(Agent::new_random(&params, &mut self.rng));
-                    }
+                    alive[idx] = false;
                 } else if tick_result.reproduce {
-                    let new_agent = agent.reproduce(&mut self.rng);
-                    self.agents.push(new_agent);
+                    newborns.push(agent.reproduce(params));
                 }
             }
+        }
 
-            idx += 1;
+        for (agent, alive) in self.agents.iter().zip(alive.iter()) {
+            if *alive {
+                continue;
+            }
+            self.statistics.deaths += 1;
+            if agent.time_alive > self.statistics.max_time_alive {
+                self.statistics.max_time_alive = agent.time_alive;
+                if self.log_records {
+                    println!(
+                        "[{}] new time alive record: {}",
+                        self.time.floor(),
+                        self.statistics.max_time_alive
+                    );
+                }
+            }
+            if agent.generation > self.statistics.max_generation {
+                self.statistics.max_generation = agent.generation;
+                if self.log_records {
+                    println!(
+                        "[{}] new generation record: {}",
+                        self.time.floor(),
+                        self.statistics.max_generation
+                    );
+                }
+            }
+        }
+
+        let mut alive = alive.into_iter();
+        self.agents.retain(|_| alive.next().unwrap());
+        for newborn in newborns {
+            if self.is_at_capacity(params) {
+                break;
+            }
+            self.add_agent(newborn);
+            self.statistics.births += 1;
+        }
+        if was_populated && self.agents.is_empty() {
+            self.statistics.extinctions += 1;
+        }
+
+        if params.evolution {
+            self.immigrate(params, d_time);
         }
 
         self.time += d_time as f64;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Gene;
+
+    fn test_world(params: &Params) -> World {
+        let mut world = World::new_seeded(params, 1);
+        for agent in world.agents.iter_mut() {
+            agent.time_since_reproduce = 0.0;
+        }
+        world
+    }
+
+    #[test]
+    fn dead_agents_are_removed_in_order() {
+        let params = Params::default();
+        let mut world = test_world(&params);
+        let generations = [1, 2, 3, 4, 5];
+        for (agent, generation) in world.agents.iter_mut().zip(generations.iter()) {
+            agent.generation = *generation;
+        }
+        world.agents[1].energy = -1.0;
+        world.agents[3].energy = -1.0;
+
+        world.tick(&params, params.tick_interval);
+
+        let survivors: Vec<u32> = world.agents.iter().map(|agent| agent.generation).collect();
+        assert_eq!(&survivors[..3], &[1, 3, 5]);
+        // The two dead agents are replaced by random agents at the end.
+        assert_eq!(world.agents.len(), 5);
+        assert_eq!(world.agents[3].generation, 1);
+        assert_eq!(world.agents[3].time_alive, 0.0);
+        assert_eq!(world.agents[4].time_alive, 0.0);
+        assert_eq!(world.statistics.max_generation, 4);
+        assert_eq!(world.statistics.deaths, 2);
+        assert_eq!(world.statistics.immigrants, 2);
+        assert_eq!(world.agents[4].origin, Origin::Immigrant);
+        assert_eq!(world.agents[4].lineage, 6);
+        assert_eq!(world.population_by_origin(), (3, 2));
+    }
+
+    #[test]
+    fn newborns_are_appended_and_not_ticked() {
+        let params = Params::default();
+        let mut world = test_world(&params);
+        world.agents[1].time_since_reproduce = 1000.0;
+        world.agents[3].time_since_reproduce = 1000.0;
+        world.agents[1].generation = 10;
+        world.agents[3].generation = 20;
+
+        world.tick(&params, params.tick_interval);
+
+        assert_eq!(world.agents.len(), 7);
+        assert_eq!(world.agents[5].generation, 11);
+        assert_eq!(world.agents[6].generation, 21);
+        assert_eq!(world.agents[5].time_alive, 0.0);
+        assert_eq!(world.agents[6].time_alive, 0.0);
+        assert_eq!(world.agents[5].pos, world.agents[1].pos);
+        assert_eq!(world.agents[5].lineage, world.agents[1].lineage);
+        assert_eq!(world.agents[5].origin, Origin::Founder);
+        assert_eq!(world.agents[5].id, 5);
+        assert_eq!(world.agents[6].id, 6);
+        assert_eq!(world.agent_by_id(6).unwrap().generation, 21);
+        assert_eq!(world.statistics.births, 2);
+    }
+
+    #[test]
+    fn no_immigrants_without_immigration() {
+        let mut params = Params::default();
+        params.immigration = Immigration::None;
+        let mut world = test_world(&params);
+        world.agents[0].energy = -1.0;
+
+        world.tick(&params, params.tick_interval);
+
+        assert_eq!(world.agents.len(), 4);
+        assert_eq!(world.statistics.immigrants, 0);
+    }
+
+    #[test]
+    fn immigrants_arrive_at_rate() {
+        let mut params = Params::default();
+        params.immigration = Immigration::Rate;
+        params.immigration_rate = 0.5;
+        let mut world = test_world(&params);
+
+        world.tick(&params, 1.0);
+        assert_eq!(world.agents.len(), 5);
+        world.tick(&params, 1.0);
+        assert_eq!(world.agents.len(), 6);
+        assert_eq!(world.agents[5].origin, Origin::Immigrant);
+        assert_eq!(world.statistics.immigrants, 1);
+    }
+
+    #[test]
+    fn carrying_capacity_limits_births() {
+        let mut params = Params::default();
+        params.carrying_capacity = Some(6);
+        let mut world = test_world(&params);
+        world.agents[1].time_since_reproduce = 1000.0;
+        world.agents[3].time_since_reproduce = 1000.0;
+
+        world.tick(&params, params.tick_interval);
+
+        assert_eq!(world.agents.len(), 6);
+        assert_eq!(world.statistics.births, 1);
+    }
+
+    #[test]
+    fn founders_are_created_from_genomes() {
+        let mut params = Params::default();
+        let source = World::new_seeded(&params, 2);
+        params.genomes = source.export_genomes()[..2].to_vec();
+        params.genomes[1].generation = 7;
+
+        let world = World::new_seeded(&params, 1);
+
+        assert_eq!(world.agents.len(), 5);
+        for (idx, agent) in world.agents.iter().enumerate() {
+            let genome = &params.genomes[idx % 2];
+            assert_eq!(agent.genes.get_size(), genome.genes[Gene::Size as usize]);
+            assert_eq!(agent.brain_weights(), &genome.weights);
+            assert_eq!(agent.generation, genome.generation);
+            assert_eq!(agent.lineage, idx as u32);
+        }
+    }
+
+    #[test]
+    fn world_starts_with_founders() {
+        let mut params = Params::default();
+        let source = World::new_seeded(&params, 2);
+        params.founders = source.export_founders();
+        params.founders[0].count = 3;
+        params.founders[1].pos = None;
+        params.founders[1].genome = None;
+
+        let world = World::new_seeded(&params, 1);
+
+        assert_eq!(world.agents.len(), 7);
+        for idx in 0..3 {
+            assert_eq!(world.agents[idx].pos, source.agents[0].pos);
+            assert_eq!(world.agents[idx].angle, source.agents[0].angle);
+            assert_eq!(
+                world.agents[idx].brain_weights(),
+                source.agents[0].brain_weights()
+            );
+            assert_eq!(world.agents[idx].lineage, idx as u32);
+        }
+        assert_ne!(world.agents[3].pos, source.agents[1].pos);
+        assert_eq!(world.agents[3].angle, source.agents[1].angle);
+        assert_ne!(
+            world.agents[3].brain_weights(),
+            source.agents[1].brain_weights()
+        );
+        assert_eq!(world.agents[6].pos, source.agents[4].pos);
+    }
+
+    #[test]
+    fn agents_are_edited_by_hand() {
+        let params = Params::default();
+        let mut world = test_world(&params);
+        let id = world.spawn_agent(&params, None, Vec2f::new(-1.0, 50.0));
+        let agent = world.agent_by_id(id).unwrap();
+        assert_eq!(agent.pos, Vec2f::new(199.0, 50.0));
+        assert_eq!(agent.origin, Origin::Immigrant);
+        assert_eq!(agent.lineage, 5);
+        assert_eq!(world.statistics.immigrants, 1);
+
+        world.move_agent(id, Vec2f::new(10.0, 20.0));
+        assert_eq!(world.agent_by_id(id).unwrap().pos, Vec2f::new(10.0, 20.0));
+        assert!(world.remove_agent(id));
+        assert!(!world.remove_agent(id));
+        assert_eq!(world.agents.len(), 5);
+        assert_eq!(world.statistics.deaths, 0);
+    }
+
+    #[test]
+    fn walls_stop_agents() {
+        let mut params = Params::default();
+        params.evolution = false;
+        let mut world = test_world(&params);
+        let center = Vec2f::new(100.0, 100.0);
+        world.agents[0].pos = center;
+        world.plant_grid.set_walls(center, 6.0, true);
+        world.plant_grid.set_walls(center, 3.0, false);
+
+        while world.time < 60.0 {
+            world.tick(&params, params.tick_interval);
+        }
+
+        assert!((world.agents[0].pos - center).magnitude() < 6.0);
+    }
+
+    #[test]
+    fn no_births_or_deaths_without_evolution() {
+        let mut params = Params::default();
+        params.evolution = false;
+        let mut world = test_world(&params);
+        world.agents[0].energy = -1.0;
+        world.agents[1].time_since_reproduce = 1000.0;
+
+        world.tick(&params, params.tick_interval);
+
+        assert_eq!(world.agents.len(), 5);
+    }
+}